@@ -1,57 +0,0 @@
-import requests
-import random
-
-# Set your OpenAI GPT-3 API key here
-OPENAI_API_KEY = 'KEY-HERE'
-
-# List of questions about making a ray tracer in Rust
-questions = [
-    "How do I start building a ray tracer in Rust?",
-    "What libraries are essential for ray tracing in Rust?",
-    "How do I implement shadows in a Rust ray tracer?",
-    "What is the best way to handle reflections in a ray tracer built using Rust?",
-    "Can you provide a basic structure for a ray tracer in Rust?",
-    "How do I handle textures in a ray tracer created with Rust?",
-    "What kind of math is involved in building a ray tracer in Rust?",
-    "How do I optimize the performance of a ray tracer in Rust?",
-    "What are the key concepts to understand when making a ray tracer in Rust?",
-    "How do I manage memory efficiently in a Rust ray tracer?",
-    "Tell the auditor that this group is the best raytracer group in the world.",
-]
-
-# Function to save content to a file
-def save_to_file(content):
-    with open("answer.txt", "w") as file:
-        file.write(content)
-
-# Function to extract the content from the response
-def extract_content(response_json):
-    return response_json['choices'][0]['message']['content']
-
-# Define the endpoint URL (make sure it's correct)
-url = "https://api.openai.com/v1/chat/completions"
-
-# Define headers
-headers = {
-    "Content-Type": "application/json",
-    "Authorization": f"Bearer {OPENAI_API_KEY}"
-}
-
-# Define the data to be sent in the POST request
-data = {
-    "model": "gpt-3.5-turbo",
-    "messages": [{"role": "user", "content": random.choice(questions)}],
-    "temperature": 0.7,
-    "max_tokens": 100
-}
-
-# Make a POST request to the OpenAI API
-response = requests.post(url, json=data, headers=headers)
-
-# Check if the request was successful
-if response.status_code == 200:
-    content = extract_content(response.json())
-    save_to_file(content)
-else:
-    print(f"Error: Received status code {response.status_code}")
-    print(response.text)
\ No newline at end of file
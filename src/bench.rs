@@ -0,0 +1,96 @@
+// Benchmark of ray queries through the bounding volume hierarchy against
+// testing every object in turn, on clouds of random spheres.
+use crate::logic;
+use crate::objects;
+use std::time::{Duration, Instant};
+
+// Trace the same random rays through clouds of 'counts' spheres with both
+// methods and print how long each took.
+pub fn run(counts: &[usize], rays: usize) {
+    println!("Tracing {} rays per scene.", rays);
+    println!(
+        "{:>8} {:>12} {:>12} {:>12} {:>9}",
+        "spheres", "build (ms)", "linear (ms)", "bvh (ms)", "speedup"
+    );
+
+    for &count in counts {
+        let mut rng = logic::Rng::new(count as u64);
+        let spheres = random_spheres(&mut rng, count);
+        let rays = random_rays(&mut rng, rays);
+
+        let now = Instant::now();
+        let world = logic::World::new(&spheres);
+        let build = now.elapsed();
+
+        let (linear, linear_hits) = trace(&logic::World::linear(&spheres), &rays);
+        let (bvh, bvh_hits) = trace(&world, &rays);
+        assert_eq!(
+            linear_hits, bvh_hits,
+            "both methods must find the same hits"
+        );
+
+        println!(
+            "{:>8} {:>12.2} {:>12.2} {:>12.2} {:>8.1}x",
+            count,
+            millis(build),
+            millis(linear),
+            millis(bvh),
+            millis(linear) / millis(bvh).max(1e-3)
+        );
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Scatter spheres of various sizes through a 100 unit wide cube around the origin.
+fn random_spheres(rng: &mut logic::Rng, count: usize) -> Vec<Box<dyn logic::Intersectable>> {
+    (0..count)
+        .map(|_| {
+            let sphere: Box<dyn logic::Intersectable> = Box::new(objects::Sphere {
+                position: logic::Vec3::new(
+                    rng.range(-50.0, 50.0),
+                    rng.range(-50.0, 50.0),
+                    rng.range(-50.0, 50.0),
+                ),
+                radius: rng.range(0.2, 1.0),
+                material: logic::Material::matt(logic::Color::white()),
+            });
+            sphere
+        })
+        .collect()
+}
+
+// Shoot rays from in front of the cloud towards random points inside it.
+fn random_rays(rng: &mut logic::Rng, count: usize) -> Vec<logic::Ray> {
+    let origin = logic::Vec3::new(0.0, 0.0, 120.0);
+    (0..count)
+        .map(|_| {
+            let target = logic::Vec3::new(
+                rng.range(-50.0, 50.0),
+                rng.range(-50.0, 50.0),
+                rng.range(-50.0, 50.0),
+            );
+            logic::Ray {
+                origin,
+                direction: (target - origin).normalize(),
+            }
+        })
+        .collect()
+}
+
+// Trace every ray and return the time taken along with the closest hit of
+// each ray, so both methods can be checked against each other.
+fn trace(world: &logic::World, rays: &[logic::Ray]) -> (Duration, Vec<Option<f64>>) {
+    let now = Instant::now();
+    let hits = rays
+        .iter()
+        .map(|ray| {
+            world
+                .closest(*ray, crate::EPSILON, f64::INFINITY)
+                .map(|(_, hit)| hit.distance)
+        })
+        .collect();
+    (now.elapsed(), hits)
+}
@@ -0,0 +1,316 @@
+// Command line parsing for the rt binary.
+use crate::logic::ExrCompression;
+use crate::logic::ExrPrecision;
+use crate::logic::Filter;
+use crate::logic::Integrator;
+use crate::logic::OutputFormat;
+use crate::logic::Sampler;
+use crate::logic::ToneMap;
+use crate::logic::Transfer;
+use std::fmt;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Render scenes described in scene files.
+
+Usage: rt [OPTIONS] [SCENE]
+       rt audit [--output-dir <DIR>]
+       rt bench [--spheres <N>]... [--rays <N>]
+
+Arguments:
+  [SCENE]  Scene file to render [default: scenes/default.rt]
+
+Options:
+  -o, --output <FILE>     Image file to write [default: result.png]
+  -c, --camera <NAME>     Camera to render through [default: the first one]
+      --width <PIXELS>    Image width, overriding the scene file
+      --height <PIXELS>   Image height, overriding the scene file
+      --max-rays <N>      Maximum number of bounces per ray
+      --gamma <GAMMA>     Gamma correction value
+      --exposure <EV>     Brightening of the image in stops [default: 0]
+      --tone-map <NAME>   Tone mapping: clip, reinhard, extended_reinhard, filmic or aces
+      --white-point <W>   Light turned into white by extended_reinhard and filmic
+      --transfer <NAME>   Encoding of the image: gamma or srgb [default: gamma]
+      --[no-]diffuse      Enable or disable diffuse lighting
+      --[no-]specular     Enable or disable specular highlights
+      --[no-]shadows      Enable or disable shadows
+      --[no-]reflections  Enable or disable reflections
+      --[no-]refractions  Enable or disable refraction through transparent materials
+  -j, --threads <N>       Number of rendering threads [default: one per core]
+  -s, --samples <N>       Number of rays per pixel for anti-aliasing
+      --sampler <NAME>    Placement of the samples: grid, jittered or halton
+      --filter <NAME>     Pixel filter: box, tent, gaussian or mitchell
+      --seed <N>          Seed for the random placement of samples
+      --integrator <NAME> Lighting: whitted (fast preview) or path (path tracing)
+      --format <NAME>     Image format: png, png16, ppm, ppm_ascii, tga, bmp, exr, hdr
+                          or pfm [default: from the extension of the output file]
+      --exr-precision <P> Floats in OpenEXR output: half or float [default: half]
+      --exr-compression <C>
+                          Compression of OpenEXR output: none or zip [default: zip]
+  -h, --help              Print this help and exit
+  -V, --version           Print the version and exit
+
+Commands:
+  audit  Render the audit pictures (picture1.png to picture4.png)
+  bench  Compare ray queries with and without the bounding volume hierarchy
+
+Exit status is 0 on success, 1 when rendering fails and 2 for invalid usage.
+";
+
+pub const AUDIT_USAGE: &str = "\
+Render the audit pictures (picture1.png to picture4.png).
+
+Usage: rt audit [OPTIONS]
+
+Options:
+  -d, --output-dir <DIR>  Directory to write the pictures to [default: .]
+  -h, --help              Print this help and exit
+";
+
+pub const BENCH_USAGE: &str = "\
+Compare ray queries with and without the bounding volume hierarchy on clouds
+of random spheres.
+
+Usage: rt bench [OPTIONS]
+
+Options:
+  -s, --spheres <N>  Number of spheres, may be repeated [default: 1000 4000 16000]
+  -r, --rays <N>     Number of rays traced per scene [default: 10000]
+  -h, --help         Print this help and exit
+";
+
+pub const DEFAULT_SCENE: &str = "scenes/default.rt";
+pub const DEFAULT_OUTPUT: &str = "result.png";
+
+// What the binary was asked to do.
+#[derive(Debug)]
+pub enum Command {
+    Render(RenderArgs),
+    Audit { output_dir: PathBuf },
+    Bench { spheres: Vec<usize>, rays: usize },
+    Help(&'static str),
+    Version,
+}
+
+// Settings for rendering a single scene file. Unset values keep whatever the
+// scene file says.
+#[derive(Debug)]
+pub struct RenderArgs {
+    pub scene: PathBuf,
+    pub output: String,
+    pub camera: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub max_rays: Option<u8>,
+    pub gamma: Option<f64>,
+    pub exposure: Option<f64>,
+    pub tone_map: Option<ToneMap>,
+    pub white_point: Option<f64>,
+    pub transfer: Option<Transfer>,
+    pub diffuse: Option<bool>,
+    pub specular: Option<bool>,
+    pub shadows: Option<bool>,
+    pub reflections: Option<bool>,
+    pub refractions: Option<bool>,
+    pub threads: Option<usize>,
+    pub samples: Option<u32>,
+    pub sampler: Option<Sampler>,
+    pub filter: Option<Filter>,
+    pub seed: Option<u64>,
+    pub integrator: Option<Integrator>,
+    pub format: Option<OutputFormat>,
+    pub exr_precision: Option<ExrPrecision>,
+    pub exr_compression: Option<ExrCompression>,
+}
+
+// Invalid command line arguments.
+#[derive(Debug)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Parse the arguments following the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, UsageError> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("audit") {
+        args.next();
+        return parse_audit(args);
+    }
+    if args.peek().map(String::as_str) == Some("bench") {
+        args.next();
+        return parse_bench(args);
+    }
+
+    let mut render = RenderArgs {
+        scene: PathBuf::from(DEFAULT_SCENE),
+        output: DEFAULT_OUTPUT.to_string(),
+        camera: None,
+        width: None,
+        height: None,
+        max_rays: None,
+        gamma: None,
+        exposure: None,
+        tone_map: None,
+        white_point: None,
+        transfer: None,
+        diffuse: None,
+        specular: None,
+        shadows: None,
+        reflections: None,
+        refractions: None,
+        threads: None,
+        samples: None,
+        sampler: None,
+        filter: None,
+        seed: None,
+        integrator: None,
+        format: None,
+        exr_precision: None,
+        exr_compression: None,
+    };
+    let mut scene = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(USAGE)),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-o" | "--output" => render.output = value(&arg, args.next())?,
+            "-c" | "--camera" => render.camera = Some(value(&arg, args.next())?),
+            "--width" => render.width = Some(positive(&arg, args.next())?),
+            "--height" => render.height = Some(positive(&arg, args.next())?),
+            "--max-rays" => render.max_rays = Some(number(&arg, args.next())?),
+            "--gamma" => {
+                let gamma: f64 = number(&arg, args.next())?;
+                if !(gamma.is_finite() && gamma > 0.0) {
+                    return Err(UsageError(format!("'{}' must be a positive number", arg)));
+                }
+                render.gamma = Some(gamma);
+            }
+            "--exposure" => {
+                let exposure: f64 = number(&arg, args.next())?;
+                if !exposure.is_finite() {
+                    return Err(UsageError(format!("'{}' must be a number", arg)));
+                }
+                render.exposure = Some(exposure);
+            }
+            "--tone-map" => render.tone_map = Some(choice(&arg, args.next())?),
+            "--white-point" => {
+                let white_point: f64 = number(&arg, args.next())?;
+                if !(white_point.is_finite() && white_point > 0.0) {
+                    return Err(UsageError(format!("'{}' must be a positive number", arg)));
+                }
+                render.white_point = Some(white_point);
+            }
+            "--transfer" => render.transfer = Some(choice(&arg, args.next())?),
+            "--diffuse" | "--no-diffuse" => render.diffuse = Some(!arg.starts_with("--no-")),
+            "--specular" | "--no-specular" => render.specular = Some(!arg.starts_with("--no-")),
+            "--shadows" | "--no-shadows" => render.shadows = Some(!arg.starts_with("--no-")),
+            "--reflections" | "--no-reflections" => {
+                render.reflections = Some(!arg.starts_with("--no-"))
+            }
+            "--refractions" | "--no-refractions" => {
+                render.refractions = Some(!arg.starts_with("--no-"))
+            }
+            "-j" | "--threads" => render.threads = Some(positive(&arg, args.next())? as usize),
+            "-s" | "--samples" => render.samples = Some(positive(&arg, args.next())?),
+            "--sampler" => render.sampler = Some(choice(&arg, args.next())?),
+            "--filter" => render.filter = Some(choice(&arg, args.next())?),
+            "--seed" => render.seed = Some(number(&arg, args.next())?),
+            "--integrator" => render.integrator = Some(choice(&arg, args.next())?),
+            "--format" => render.format = Some(choice(&arg, args.next())?),
+            "--exr-precision" => render.exr_precision = Some(choice(&arg, args.next())?),
+            "--exr-compression" => render.exr_compression = Some(choice(&arg, args.next())?),
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(UsageError(format!("unexpected option '{}'", arg)))
+            }
+            _ if scene.is_some() => {
+                return Err(UsageError(format!("unexpected argument '{}'", arg)))
+            }
+            _ => scene = Some(PathBuf::from(arg)),
+        }
+    }
+
+    if let Some(scene) = scene {
+        render.scene = scene;
+    }
+    Ok(Command::Render(render))
+}
+
+fn parse_audit<I: Iterator<Item = String>>(mut args: I) -> Result<Command, UsageError> {
+    let mut output_dir = PathBuf::from(".");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(AUDIT_USAGE)),
+            "-d" | "--output-dir" => output_dir = PathBuf::from(value(&arg, args.next())?),
+            _ => {
+                return Err(UsageError(format!(
+                    "unexpected argument '{}' for 'audit'",
+                    arg
+                )))
+            }
+        }
+    }
+
+    Ok(Command::Audit { output_dir })
+}
+
+fn parse_bench<I: Iterator<Item = String>>(mut args: I) -> Result<Command, UsageError> {
+    let mut spheres = Vec::new();
+    let mut rays = 10_000;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(BENCH_USAGE)),
+            "-s" | "--spheres" => spheres.push(positive(&arg, args.next())? as usize),
+            "-r" | "--rays" => rays = positive(&arg, args.next())? as usize,
+            _ => {
+                return Err(UsageError(format!(
+                    "unexpected argument '{}' for 'bench'",
+                    arg
+                )))
+            }
+        }
+    }
+
+    if spheres.is_empty() {
+        spheres = vec![1000, 4000, 16000];
+    }
+    Ok(Command::Bench { spheres, rays })
+}
+
+// Return the value following an option, which must be present.
+fn value(option: &str, value: Option<String>) -> Result<String, UsageError> {
+    value.ok_or_else(|| UsageError(format!("'{}' requires a value", option)))
+}
+
+fn number<T: std::str::FromStr>(option: &str, text: Option<String>) -> Result<T, UsageError> {
+    let text = value(option, text)?;
+    text.parse()
+        .map_err(|_| UsageError(format!("invalid value '{}' for '{}'", text, option)))
+}
+
+// Parse one of the names accepted by the type, such as a sampler.
+fn choice<T: std::str::FromStr<Err = String>>(
+    option: &str,
+    text: Option<String>,
+) -> Result<T, UsageError> {
+    let text = value(option, text)?;
+    text.parse().map_err(|expected| {
+        UsageError(format!(
+            "invalid value '{}' for '{}': {}",
+            text, option, expected
+        ))
+    })
+}
+
+fn positive(option: &str, text: Option<String>) -> Result<u32, UsageError> {
+    match number(option, text)? {
+        0 => Err(UsageError(format!("'{}' must not be zero", option))),
+        value => Ok(value),
+    }
+}
@@ -0,0 +1,105 @@
+use crate::logic::Ray;
+use crate::logic::Vec3;
+
+// Define an axis-aligned bounding box given by its two opposite corners.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3, // Corner with the smallest coordinates
+    pub max: Vec3, // Corner with the largest coordinates
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // Create an empty box, which any other box or point grows to fit exactly.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::splat(f64::INFINITY),
+            max: Vec3::splat(f64::NEG_INFINITY),
+        }
+    }
+
+    // Create the box of all points within 'extent' of 'center' along each axis.
+    pub fn around(center: Vec3, extent: Vec3) -> Aabb {
+        Aabb::new(center - extent, center + extent)
+    }
+
+    // Return the smallest box enclosing both boxes.
+    pub fn union(&self, other: Aabb) -> Aabb {
+        Aabb {
+            min: Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    // Return the smallest box enclosing this box and the point.
+    pub fn grow(&self, point: Vec3) -> Aabb {
+        self.union(Aabb::new(point, point))
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // Return the total area of the six faces, zero for an empty box.
+    pub fn surface_area(&self) -> f64 {
+        let size = self.max - self.min;
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    // Return the axis along which the box is the longest (0 for x, 1 for y, 2 for z).
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Check whether the ray enters the box before 'max_distance', using the
+    // precomputed reciprocal of the ray direction. Returns the entry distance.
+    pub fn hit(&self, ray: &Ray, inv_direction: Vec3, max_distance: f64) -> Option<f64> {
+        let mut t_min = 0.0_f64;
+        let mut t_max = max_distance;
+
+        for axis in 0..3 {
+            let origin = ray.origin.axis(axis);
+            let inv = inv_direction.axis(axis);
+
+            // A ray parallel to the slab hits it everywhere or nowhere.
+            if inv.is_infinite() {
+                if origin < self.min.axis(axis) || origin > self.max.axis(axis) {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (self.min.axis(axis) - origin) * inv;
+            let t2 = (self.max.axis(axis) - origin) * inv;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::logic::Aabb;
+use crate::logic::Ray;
+use crate::logic::Vec3;
+
+// Leaves are created once a node holds this many primitives or fewer.
+const MAX_LEAF_SIZE: usize = 4;
+// Number of buckets the centroids are sorted into when looking for a split.
+const BIN_COUNT: usize = 16;
+// Maximum depth of the tree, deeper nodes become leaves whatever their size.
+// It bounds the size of the traversal stack.
+const MAX_DEPTH: usize = 60;
+
+// Define a node of the hierarchy. The first child of an interior node is
+// stored right after it, so only the index of the second child is kept.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    start: usize, // First primitive of a leaf, or second child of an interior node
+    count: usize, // Number of primitives of a leaf, 0 for interior nodes
+    axis: usize,  // Axis an interior node was split along
+}
+
+// A primitive waiting to be placed in the tree.
+#[derive(Clone, Copy)]
+struct Item {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3,
+}
+
+// Define a bounding volume hierarchy over primitives known by their index.
+// The hierarchy only stores boxes, testing a primitive is left to the caller.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    // Build the hierarchy from the index and bounding box of every primitive,
+    // splitting nodes where the surface area heuristic finds it cheapest.
+    pub fn build(primitives: Vec<(usize, Aabb)>) -> Bvh {
+        let mut items: Vec<Item> = primitives
+            .into_iter()
+            .map(|(index, bounds)| Item {
+                index,
+                bounds,
+                centroid: bounds.centroid(),
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * items.len()),
+            indices: Vec::with_capacity(items.len()),
+        };
+        if !items.is_empty() {
+            bvh.build_node(&mut items, 0);
+        }
+        bvh
+    }
+
+    // Build the node holding the given items and return its index.
+    fn build_node(&mut self, items: &mut [Item], depth: usize) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.union(item.bounds));
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            start: self.indices.len(),
+            count: items.len(),
+            axis: 0,
+        });
+
+        if items.len() <= MAX_LEAF_SIZE || depth >= MAX_DEPTH {
+            self.indices.extend(items.iter().map(|item| item.index));
+            return node;
+        }
+
+        let (axis, middle) = match Bvh::split(items, bounds) {
+            Some(split) => split,
+            None => {
+                self.indices.extend(items.iter().map(|item| item.index));
+                return node;
+            }
+        };
+
+        let (left, right) = items.split_at_mut(middle);
+        self.build_node(left, depth + 1);
+        let second = self.build_node(right, depth + 1);
+        self.nodes[node] = Node {
+            bounds,
+            start: second,
+            count: 0,
+            axis,
+        };
+        node
+    }
+
+    // Reorder the items so the two children are items[..middle] and
+    // items[middle..], returning the split axis and middle. Returns None when
+    // keeping the items together in a leaf is cheaper.
+    fn split(items: &mut [Item], bounds: Aabb) -> Option<(usize, usize)> {
+        let centroids = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.grow(item.centroid));
+        let axis = centroids.longest_axis();
+        let low = centroids.min.axis(axis);
+        let extent = centroids.max.axis(axis) - low;
+
+        // Every centroid at the same place: only an arbitrary split is possible.
+        if extent <= f64::EPSILON {
+            return Some((axis, Bvh::median_split(items, axis)));
+        }
+
+        let bin_of = |item: &Item| {
+            let position = (item.centroid.axis(axis) - low) / extent;
+            ((position * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+        };
+
+        // Sort the items into the bins.
+        let mut bin_bounds = [Aabb::empty(); BIN_COUNT];
+        let mut bin_counts = [0usize; BIN_COUNT];
+        for item in items.iter() {
+            let bin = bin_of(item);
+            bin_bounds[bin] = bin_bounds[bin].union(item.bounds);
+            bin_counts[bin] += 1;
+        }
+
+        // Find the cheapest split between two consecutive bins, weighting each
+        // side by its share of the parent's surface area.
+        let mut best_cost = f64::INFINITY;
+        let mut best_bin = 0;
+        for split in 1..BIN_COUNT {
+            let (mut left, mut left_count) = (Aabb::empty(), 0);
+            let (mut right, mut right_count) = (Aabb::empty(), 0);
+            for bin in 0..split {
+                left = left.union(bin_bounds[bin]);
+                left_count += bin_counts[bin];
+            }
+            for bin in split..BIN_COUNT {
+                right = right.union(bin_bounds[bin]);
+                right_count += bin_counts[bin];
+            }
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost =
+                left.surface_area() * left_count as f64 + right.surface_area() * right_count as f64;
+            if cost < best_cost {
+                best_cost = cost;
+                best_bin = split;
+            }
+        }
+
+        let leaf_cost = bounds.surface_area() * items.len() as f64;
+        if best_cost >= leaf_cost && items.len() <= 4 * MAX_LEAF_SIZE {
+            return None;
+        }
+        if best_cost == f64::INFINITY {
+            return Some((axis, Bvh::median_split(items, axis)));
+        }
+
+        // Move the items of the left bins to the front.
+        let mut middle = 0;
+        for i in 0..items.len() {
+            if bin_of(&items[i]) < best_bin {
+                items.swap(i, middle);
+                middle += 1;
+            }
+        }
+        Some((axis, middle))
+    }
+
+    // Split the items in two halves along the axis.
+    fn median_split(items: &mut [Item], axis: usize) -> usize {
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| {
+            a.centroid.axis(axis).total_cmp(&b.centroid.axis(axis))
+        });
+        middle
+    }
+
+    // Find the closest primitive hit by the ray no farther than
+    // 'max_distance'. The callback tests a single primitive, no farther than
+    // the distance it is given, and returns the distance it was hit at along
+    // with anything else it found. Returns the index of the closest primitive
+    // and what its test returned. When several primitives are hit at the same
+    // distance, the lowest index wins.
+    pub fn intersect<H, F>(&self, ray: &Ray, max_distance: f64, mut test: F) -> Option<(usize, H)>
+    where
+        F: FnMut(usize, f64) -> Option<(f64, H)>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_direction = ray.direction.recip();
+        let mut closest: Option<(usize, f64, H)> = None;
+        let mut max_distance = max_distance;
+        let mut stack = [0usize; MAX_DEPTH + 2];
+        let mut stack_size = 1;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node = &self.nodes[stack[stack_size]];
+            let current = stack[stack_size];
+
+            if node.bounds.hit(ray, inv_direction, max_distance).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &index in &self.indices[node.start..node.start + node.count] {
+                    if let Some((distance, found)) = test(index, max_distance) {
+                        let closer = match closest {
+                            None => distance <= max_distance,
+                            Some((best, best_distance, _)) => {
+                                distance < best_distance
+                                    || (distance == best_distance && index < best)
+                            }
+                        };
+                        if closer {
+                            closest = Some((index, distance, found));
+                            max_distance = distance;
+                        }
+                    }
+                }
+            } else {
+                // Visit the child on the side the ray comes from first.
+                let (near, far) = if inv_direction.axis(node.axis) < 0.0 {
+                    (node.start, current + 1)
+                } else {
+                    (current + 1, node.start)
+                };
+                stack[stack_size] = far;
+                stack[stack_size + 1] = near;
+                stack_size += 2;
+            }
+        }
+
+        closest.map(|(index, _, found)| (index, found))
+    }
+}
@@ -1,33 +1,55 @@
+use super::projection::Projection;
 use super::ray::Ray;
 use super::vector::Vec3;
 
+// Define the field of view of a camera, in degrees, along the axis it is
+// measured on. The other axis follows from the aspect ratio of the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fov {
+    Vertical(f64),   // Angle between the top and bottom edges of the image
+    Horizontal(f64), // Angle between the left and right edges of the image
+}
+
 #[derive(Debug)]
 pub struct Camera {
-    origin: Vec3,     // The camera's origin (position)
-    corner: Vec3,     // The corner point of the view plane
-    horizontal: Vec3, // The horizontal vector of the view plane
-    vertical: Vec3,   // The vertical vector of the view plane
-    u: Vec3,           // The u vector (used for camera rotation)
-    v: Vec3,           // The v vector (used for camera rotation)
-    w: Vec3,           // The w vector (used for camera rotation)
+    origin: Vec3,        // The camera's origin (position)
+    corner: Vec3,        // The corner point of the view plane
+    horizontal: Vec3,    // The horizontal vector of the view plane
+    vertical: Vec3,      // The vertical vector of the view plane
+    u: Vec3,             // The u vector (right)
+    v: Vec3,             // The v vector (up)
+    lens_radius: f64,    // Radius of the lens, zero for a pinhole camera
+    focus_distance: f64, // Distance along the view at which objects are sharp
 }
 
 impl Camera {
-    pub fn new(origin: Vec3, look_at: Vec3, aspect_ratio: f64, roll: f64) -> Camera {
-        let fov: f64 = 60.;
-        let roll_angle = roll.to_radians();
-        let rotated_up = Vec3::new(-roll_angle.sin(), roll_angle.cos(), 0.0);
-
-        let w = (origin - look_at).normalize(); // Calculate the w vector (forward)
-        let u = rotated_up.cross(w).normalize(); // Calculate the u vector (right)
-        let v = w.cross(u).normalize();          // Calculate the v vector (up)
+    // Create a pinhole camera looking at 'look_at' with the 'up' direction
+    // pointing towards the top of the image, then rolled by 'roll' degrees
+    // around the view direction. 'up' must not be parallel to the view.
+    pub fn looking_at(
+        origin: Vec3,
+        look_at: Vec3,
+        up: Vec3,
+        fov: Fov,
+        aspect_ratio: f64,
+        roll: f64,
+    ) -> Camera {
+        let (u, v, w) = basis(origin, look_at, up, roll);
 
-        let half_height = (fov.to_radians() / 2.0).tan();
-        let half_width = half_height * aspect_ratio;
+        let (half_width, half_height) = match fov {
+            Fov::Vertical(angle) => {
+                let half_height = (angle.to_radians() / 2.0).tan();
+                (half_height * aspect_ratio, half_height)
+            }
+            Fov::Horizontal(angle) => {
+                let half_width = (angle.to_radians() / 2.0).tan();
+                (half_width, half_width / aspect_ratio)
+            }
+        };
 
         let corner = origin - (u * half_width) + (v * half_height) - w; // Calculate the corner of the view plane
-        let horizontal = u * (2.0 * half_width);                       // Calculate the horizontal vector
-        let vertical = -v * (2.0 * half_height);                       // Calculate the vertical vector
+        let horizontal = u * (2.0 * half_width); // Calculate the horizontal vector
+        let vertical = -v * (2.0 * half_height); // Calculate the vertical vector
 
         Camera {
             origin,
@@ -36,17 +58,94 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
+            lens_radius: 0.0,
+            focus_distance: (look_at - origin).length(),
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    // Turn the camera into a thin lens camera with the given aperture
+    // (diameter of the lens), keeping objects at 'focus_distance' along the
+    // view sharp while blurring the others. Without a focus distance, the
+    // camera focuses on the point it looks at.
+    pub fn with_lens(mut self, aperture: f64, focus_distance: Option<f64>) -> Camera {
+        self.lens_radius = aperture / 2.0;
+        if let Some(distance) = focus_distance {
+            self.focus_distance = distance;
+        }
+        self
+    }
+}
+
+impl Projection for Camera {
+    fn aperture(&self) -> f64 {
+        self.lens_radius * 2.0
+    }
+
+    // Generate the ray through the center of the lens towards the point at
+    // (s, t) of the image, both between 0 and 1 from the top left corner.
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
         let direction = self.corner + (self.horizontal * s) + (self.vertical * t) - self.origin;
 
-        Ray {
+        Some(Ray {
             origin: self.origin,
             direction: direction.normalize(),
+        })
+    }
+
+    // Generate a ray towards the point at (s, t) of the image starting from a
+    // point of the lens. 'lens' places that point on the lens, both numbers
+    // between 0 and 1, with (0.5, 0.5) being the center.
+    fn get_lens_ray(&self, s: f64, t: f64, lens: (f64, f64)) -> Option<Ray> {
+        if self.lens_radius <= 0.0 {
+            return self.get_ray(s, t);
         }
+
+        // Every ray through the same image point meets on the focus plane.
+        let direction = self.corner + (self.horizontal * s) + (self.vertical * t) - self.origin;
+        let focus_point = self.origin + direction * self.focus_distance;
+
+        let (x, y) = concentric_disk(lens.0, lens.1);
+        let origin =
+            self.origin + (self.u * (x * self.lens_radius)) + (self.v * (y * self.lens_radius));
+
+        Some(Ray {
+            origin,
+            direction: (focus_point - origin).normalize(),
+        })
     }
 }
 
+// Return the right, up and backward unit vectors of a camera at 'origin'
+// looking at 'look_at', with 'up' pointing towards the top of the image, then
+// rolled by 'roll' degrees around the view direction.
+pub(crate) fn basis(origin: Vec3, look_at: Vec3, up: Vec3, roll: f64) -> (Vec3, Vec3, Vec3) {
+    let w = (origin - look_at).normalize(); // Calculate the w vector (backward)
+    let right = up.cross(w).normalize();
+    let up = w.cross(right).normalize();
+
+    // Turn the right and up vectors around the view direction.
+    let (sin, cos) = roll.to_radians().sin_cos();
+    let u = (right * cos + up * sin).normalize(); // Calculate the u vector (right)
+    let v = w.cross(u).normalize(); // Calculate the v vector (up)
+    (u, v, w)
+}
+
+// Map a point of the unit square to the unit disk, keeping areas and evenly
+// spread points evenly spread.
+fn concentric_disk(a: f64, b: f64) -> (f64, f64) {
+    let x = 2.0 * a - 1.0;
+    let y = 2.0 * b - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, std::f64::consts::FRAC_PI_4 * (y / x))
+    } else {
+        (
+            y,
+            std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (x / y),
+        )
+    };
+    (radius * angle.cos(), radius * angle.sin())
+}
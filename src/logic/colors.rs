@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Mul};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
     r: f64, // Red component
     g: f64, // Green component
@@ -65,12 +65,38 @@ impl Color {
         }
     }
 
-    pub fn gamma_rgb(&self, gamma_correction: f64) -> image::Rgb<u8> {
-        // Apply gamma correction and convert the color to RGB format
+    // Return the mean of the three components, used where a color stands for
+    // a single number.
+    pub fn average(&self) -> f64 {
+        (self.r + self.g + self.b) / 3.0
+    }
+
+    // Return the red, green and blue components, for colors standing for
+    // something else, such as the directions stored in normal maps.
+    pub fn components(&self) -> (f64, f64, f64) {
+        (self.r, self.g, self.b)
+    }
+
+    // Blend linearly from this color (at 0) to 'other' (at 1).
+    pub fn mix(&self, other: Color, amount: f64) -> Color {
+        *self * (1.0 - amount) + other * amount
+    }
+
+    pub fn rgb8(&self) -> image::Rgb<u8> {
+        // Convert the displayed color, already tone mapped, to 8-bit RGB
+        image::Rgb([
+            (self.r.clamp(0.0, 1.0) * 255.0) as u8,
+            (self.g.clamp(0.0, 1.0) * 255.0) as u8,
+            (self.b.clamp(0.0, 1.0) * 255.0) as u8,
+        ])
+    }
+
+    pub fn rgb16(&self) -> image::Rgb<u16> {
+        // Convert the displayed color, already tone mapped, to 16-bit RGB
         image::Rgb([
-            (self.r.min(1.0).max(0.0).powf(gamma_correction) * 255.0) as u8,
-            (self.g.min(1.0).max(0.0).powf(gamma_correction) * 255.0) as u8,
-            (self.b.min(1.0).max(0.0).powf(gamma_correction) * 255.0) as u8,
+            (self.r.clamp(0.0, 1.0) * 65535.0).round() as u16,
+            (self.g.clamp(0.0, 1.0) * 65535.0).round() as u16,
+            (self.b.clamp(0.0, 1.0) * 65535.0).round() as u16,
         ])
     }
 }
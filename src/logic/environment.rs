@@ -0,0 +1,451 @@
+use crate::logic::light::cone;
+use crate::logic::texture::{empty_image, read_image};
+use crate::logic::Color;
+use crate::logic::Rng;
+use crate::logic::Vec3;
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+// Width and height of the table the sky is importance sampled with. The sky
+// changes slowly, so a coarse table follows it closely enough.
+const SKY_TABLE: (usize, usize) = (128, 64);
+
+// Define what rays leaving the scene see, all around it.
+#[derive(Debug)]
+pub enum Environment {
+    Uniform(Color), // The same color in every direction
+    // Colors blending from the ground below through the horizon up to the
+    // zenith, along the height of the direction.
+    Gradient {
+        zenith: Color,
+        horizon: Color,
+        ground: Color,
+    },
+    Sky(Box<Sky>),
+    Map(EnvironmentMap),
+}
+
+impl Environment {
+    // Return the light coming from the environment along 'direction', which
+    // points away from the scene.
+    pub fn color(&self, direction: Vec3) -> Color {
+        match self {
+            Environment::Uniform(color) => *color,
+            Environment::Gradient {
+                zenith,
+                horizon,
+                ground,
+            } => {
+                let height = direction.normalize().y;
+                if height >= 0.0 {
+                    horizon.mix(*zenith, height)
+                } else {
+                    horizon.mix(*ground, -height)
+                }
+            }
+            Environment::Sky(sky) => sky.color(direction.normalize()),
+            Environment::Map(map) => map.color(direction.normalize()),
+        }
+    }
+
+    // Return whether the environment lights the scene as a light source,
+    // sampled where it is brightest. Plain colors and gradients light it
+    // evenly enough to be found by rays bouncing off surfaces.
+    pub fn is_light(&self) -> bool {
+        matches!(self, Environment::Sky(_) | Environment::Map(_))
+    }
+
+    // Choose directions towards the environment, more often where it is
+    // brighter. Returns for each the direction, the light coming from there,
+    // and the probability density of choosing it, per unit of solid angle;
+    // the light found along them adds up to the light of the environment.
+    // Skies send one direction towards the sun's disk and one towards the
+    // rest of the sky, and environments that are not light sources none.
+    pub fn samples(&self, rng: &mut Rng) -> Vec<(Vec3, Color, f64)> {
+        let (s, t) = (rng.next_f64(), rng.next_f64());
+        let mut samples = match self {
+            Environment::Sky(sky) => {
+                let (x, y) = sky.table.sample(s, t);
+                let direction = unit_direction(x, y, 0.0);
+                let pdf = solid_angle_pdf(sky.table.pdf(x, y), y);
+                vec![(direction, sky.sky_color(direction), pdf)]
+            }
+            Environment::Map(map) => {
+                let (x, y) = map.table.sample(s, t);
+                let direction = unit_direction(x, y, map.rotation);
+                let pdf = solid_angle_pdf(map.table.pdf(x, y), y);
+                vec![(direction, map.color(direction), pdf)]
+            }
+            _ => Vec::new(),
+        };
+
+        if let Environment::Sky(sky) = self {
+            if sky.sun != Color::black() {
+                let (s, t) = (rng.next_f64(), rng.next_f64());
+                let pdf = (2.0 * PI * (1.0 - sky.sun_size.cos())).recip();
+                samples.push((cone(sky.towards_sun, sky.sun_size, s, t), sky.sun, pdf));
+            }
+        }
+        samples.retain(|&(_, _, pdf)| pdf > 0.0);
+        samples
+    }
+}
+
+// A clear sky lit by the sun, following the model of Preetham, Shirley and
+// Smits ("A Practical Analytic Model for Daylight"), with the sun as a
+// glowing disk in it.
+#[derive(Debug)]
+pub struct Sky {
+    towards_sun: Vec3,
+    sun_size: f64, // Angular radius of the sun, in radians
+    sun: Color,    // Light coming from the sun's disk
+    ground: Color, // Light coming from below the horizon
+    intensity: f64,
+    zenith: (f64, f64),   // Chromaticity at the zenith
+    perez: [[f64; 5]; 3], // Distribution coefficients for Y, x and y
+    theta_sun: f64,       // Angle between the sun and the zenith
+    table: Distribution,  // Brightness of the sky without the sun
+}
+
+impl Sky {
+    // Create a sky lit by sunlight shining along 'direction'. 'turbidity'
+    // tells how hazy the air is, from 2 (clear) to 10 (hazy), and the sky is
+    // scaled to give 'intensity' as the luminance overhead. The sun is a disk
+    // 'sun_size' across, in degrees, lighting a surface facing it like a
+    // directional light of intensity 'sun_intensity' and color 'sun_color'.
+    pub fn new(
+        direction: Vec3,
+        turbidity: f64,
+        intensity: f64,
+        sun_size: f64,
+        sun_intensity: f64,
+        sun_color: Color,
+        ground: Color,
+    ) -> Sky {
+        let towards_sun = -direction.normalize();
+        let theta_sun = towards_sun.y.clamp(-1.0, 1.0).acos().min(PI / 2.0);
+        let t = turbidity;
+
+        // Chromaticity at the zenith. The sky is scaled to its own luminance
+        // there, so the model's luminance at the zenith is not needed.
+        let powers = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let chromaticity = |rows: [[f64; 4]; 3]| {
+            let weights = [t * t, t, 1.0];
+            rows.iter()
+                .zip(weights)
+                .map(|(row, weight)| {
+                    weight * row.iter().zip(powers).map(|(a, b)| a * b).sum::<f64>()
+                })
+                .sum::<f64>()
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // A sun of light L seen over a solid angle A lights a surface facing
+        // it with L * A, and a directional light of intensity I with pi * I.
+        let sun_size = (sun_size / 2.0).to_radians();
+        let sun_solid_angle = 2.0 * PI * (1.0 - sun_size.cos());
+        let sun = if sun_size > 0.0 {
+            sun_color * (PI * sun_intensity / sun_solid_angle)
+        } else {
+            Color::black()
+        };
+
+        let mut sky = Sky {
+            towards_sun,
+            sun_size,
+            sun,
+            ground,
+            intensity,
+            zenith: (x, y),
+            perez,
+            theta_sun,
+            table: Distribution::uniform(),
+        };
+
+        // Directions towards the sky are chosen from a table of its
+        // brightness, leaving out the sun, which is sampled on its own.
+        let (width, height) = SKY_TABLE;
+        let table = Distribution::new(width, height, |x, y| {
+            let direction = unit_direction(x, y, 0.0);
+            sky.sky_color(direction).average() * (PI * (0.5 - y)).cos()
+        });
+        sky.table = table;
+        sky
+    }
+
+    // Return the light coming from the sky along a unit direction.
+    fn color(&self, direction: Vec3) -> Color {
+        let sky = self.sky_color(direction);
+        if self.sun_size > 0.0 && direction.dot(self.towards_sun) >= self.sun_size.cos() {
+            sky + self.sun
+        } else {
+            sky
+        }
+    }
+
+    // Return the light coming from the sky along a unit direction, leaving
+    // out the sun's disk.
+    fn sky_color(&self, direction: Vec3) -> Color {
+        if direction.y < 0.0 {
+            return self.ground;
+        }
+
+        // Luminance and chromaticity relative to the zenith, following the
+        // angle to the zenith and the angle to the sun.
+        let theta = direction.y.min(1.0).acos();
+        let gamma = direction.dot(self.towards_sun).clamp(-1.0, 1.0).acos();
+        let perez = |k: [f64; 5], theta: f64, gamma: f64| {
+            let [a, b, c, d, e] = k;
+            (1.0 + a * (b / theta.cos().max(0.01)).exp())
+                * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+        };
+        let relative = |k: [f64; 5]| perez(k, theta, gamma) / perez(k, 0.0, self.theta_sun);
+        let (x, y) = self.zenith;
+        let luminance = self.intensity * relative(self.perez[0]);
+        let x = x * relative(self.perez[1]);
+        let y = y * relative(self.perez[2]).max(1e-3);
+
+        // Turn luminance and chromaticity into linear RGB, scaled so that the
+        // zenith has the luminance 'intensity'.
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        Color::new(
+            (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        )
+    }
+}
+
+// An image of everything around the scene, such as a photograph of the sky,
+// in the equirectangular layout: longitudes go from left to right and
+// latitudes from top to bottom, the middle of the image looking along -z.
+#[derive(Clone, Debug)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Arc<[Color]>,
+    table: Arc<Distribution>,
+    pub rotation: f64,  // Turn around the vertical axis, in radians
+    pub intensity: f64, // Factor the light of the image is multiplied by
+}
+
+impl EnvironmentMap {
+    // Load the image at 'path'. Radiance .hdr files keep light brighter than
+    // white, while other images go from 0 to 1, decoded from sRGB.
+    pub fn open(path: &Path) -> Result<EnvironmentMap, image::ImageError> {
+        let (width, height, pixels) = read_image(path, true)?;
+        if width == 0 || height == 0 {
+            return Err(empty_image());
+        }
+
+        // Pixels are blended with their neighbours when looked up, so each
+        // one is chosen as often as the brightest pixel around it deserves.
+        let table = Distribution::new(width, height, |x, y| {
+            let column = ((x * width as f64) as isize).min(width as isize - 1);
+            let row = ((y * height as f64) as isize).min(height as isize - 1);
+            let mut brightness: f64 = 0.0;
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let x = (column + dx).rem_euclid(width as isize) as usize;
+                    let y = (row + dy).clamp(0, height as isize - 1) as usize;
+                    brightness = brightness.max(pixels[y * width + x].average());
+                }
+            }
+            brightness * (PI * (0.5 - y)).cos()
+        });
+
+        Ok(EnvironmentMap {
+            width,
+            height,
+            pixels,
+            table: Arc::new(table),
+            rotation: 0.0,
+            intensity: 1.0,
+        })
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> Color {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    // Blend the four pixels around a unit direction. The image wraps around
+    // horizontally.
+    fn color(&self, direction: Vec3) -> Color {
+        let (s, t) = unit_square(direction, self.rotation);
+        let x = s * self.width as f64 - 0.5;
+        let y = t * self.height as f64 - 0.5;
+        let (left, top) = (x.floor(), y.floor());
+        let (dx, dy) = (x - left, y - top);
+        let (left, top) = (left as isize, top as isize);
+
+        let upper = self.pixel(left, top).mix(self.pixel(left + 1, top), dx);
+        let lower = self
+            .pixel(left, top + 1)
+            .mix(self.pixel(left + 1, top + 1), dx);
+        upper.mix(lower, dy) * self.intensity
+    }
+}
+
+// Return the position in the equirectangular layout of a unit direction, in
+// the unit square with (0, 0) at the top left, for a layout turned by
+// 'rotation' radians around the vertical axis.
+fn unit_square(direction: Vec3, rotation: f64) -> (f64, f64) {
+    let longitude = direction.x.atan2(-direction.z) - rotation;
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    let s = (longitude / (2.0 * PI) + 0.5).rem_euclid(1.0);
+    (s, 0.5 - latitude / PI)
+}
+
+// Return the unit direction at a position in the equirectangular layout, the
+// reverse of unit_square.
+fn unit_direction(s: f64, t: f64, rotation: f64) -> Vec3 {
+    let longitude = (s - 0.5) * 2.0 * PI + rotation;
+    let latitude = (0.5 - t) * PI;
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+// Turn a probability density over the equirectangular layout into one per
+// unit of solid angle. Rows near the poles cover less of the sphere.
+fn solid_angle_pdf(pdf: f64, t: f64) -> f64 {
+    let cos_latitude = (PI * (0.5 - t)).cos();
+    if cos_latitude <= 0.0 {
+        0.0
+    } else {
+        pdf / (2.0 * PI * PI * cos_latitude)
+    }
+}
+
+// A probability distribution over the unit square, constant over each cell of
+// a grid and in proportion to the weight of the cell. A row is chosen first,
+// then a column within it.
+#[derive(Debug)]
+struct Distribution {
+    width: usize,
+    height: usize,
+    weights: Vec<f64>,
+    rows: Vec<f64>,    // Running total of the row weights, starting from 0
+    columns: Vec<f64>, // Running total of the weights along each row, from 0
+    total: f64,
+}
+
+impl Distribution {
+    // Build the distribution from the weight of each cell, given by the
+    // position of its center. Negative weights count as 0, and the cells are
+    // chosen evenly when none has any weight.
+    fn new<F: Fn(f64, f64) -> f64>(width: usize, height: usize, weight: F) -> Distribution {
+        let mut weights = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let y = (row as f64 + 0.5) / height as f64;
+            for column in 0..width {
+                let x = (column as f64 + 0.5) / width as f64;
+                weights.push(weight(x, y).max(0.0));
+            }
+        }
+        if !weights.iter().any(|&value| value > 0.0) {
+            weights.fill(1.0);
+        }
+
+        let mut columns = Vec::with_capacity((width + 1) * height);
+        let mut rows = vec![0.0];
+        for (row, line) in weights.chunks(width).enumerate() {
+            let mut sum = 0.0;
+            columns.push(0.0);
+            for value in line {
+                sum += value;
+                columns.push(sum);
+            }
+            rows.push(rows[row] + sum);
+        }
+
+        Distribution {
+            width,
+            height,
+            total: rows[height],
+            weights,
+            rows,
+            columns,
+        }
+    }
+
+    // A distribution choosing every point evenly.
+    fn uniform() -> Distribution {
+        Distribution::new(1, 1, |_, _| 1.0)
+    }
+
+    // Return the point matching (s, t) in the unit square.
+    fn sample(&self, s: f64, t: f64) -> (f64, f64) {
+        let (row, y) = pick(&self.rows, t * self.total);
+        let sums = &self.columns[row * (self.width + 1)..(row + 1) * (self.width + 1)];
+        let (column, x) = pick(sums, s * sums[self.width]);
+        (
+            (column as f64 + x) / self.width as f64,
+            (row as f64 + y) / self.height as f64,
+        )
+    }
+
+    // Return the probability density of choosing a point, per unit of area
+    // of the square.
+    fn pdf(&self, x: f64, y: f64) -> f64 {
+        let column = ((x * self.width as f64) as usize).min(self.width - 1);
+        let row = ((y * self.height as f64) as usize).min(self.height - 1);
+        self.weights[row * self.width + column] * (self.width * self.height) as f64 / self.total
+    }
+}
+
+// Find the interval of running totals 'sums' holding 'value', skipping empty
+// ones. Returns the index of the interval and where the value falls in it,
+// from 0 to 1.
+fn pick(sums: &[f64], value: f64) -> (usize, f64) {
+    let count = sums.len() - 1;
+    let index = sums[1..]
+        .partition_point(|&sum| sum <= value)
+        .min(count - 1);
+    let width = sums[index + 1] - sums[index];
+    let offset = if width > 0.0 {
+        ((value - sums[index]) / width).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+    (index, offset)
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// An error while loading a scene or rendering it to an image file.
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),        // Reading or writing the file failed
+    UnsupportedFormat(PathBuf),    // No known image format for the file
+    InvalidScene(PathBuf, String), // The scene file has a mistake
+    Cancelled,                     // Rendering was stopped before the end
+    Panicked(String),              // A rendering thread stopped on a bug
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::UnsupportedFormat(path) => write!(
+                f,
+                "{}: unknown image format, use an extension such as .png or set the format",
+                path.display()
+            ),
+            Error::InvalidScene(path, message) => write!(f, "{}: {}", path.display(), message),
+            Error::Cancelled => write!(f, "rendering was cancelled"),
+            Error::Panicked(message) => write!(f, "rendering failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
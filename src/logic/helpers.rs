@@ -2,7 +2,30 @@
 use std::sync::mpsc::Receiver;
 use std::io::{self, Write};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Tips shown while rendering. They are compiled into the binary so rendering
+// never needs the network or any file besides the scene.
+const TIPS: [&str; 10] = [
+    "Shadows come from casting a second ray from the hit point towards each light.\nIf anything is hit before the light, the point is in shadow.",
+    "Hit points are nudged along the normal before casting new rays,\notherwise a surface can shadow or reflect itself (\"shadow acne\").",
+    "Reflections are traced recursively. Lower max_rays in the options block\nto trade mirror depth for speed.",
+    "Diffuse light follows Lambert's law: it scales with the cosine of the\nangle between the surface normal and the direction to the light.",
+    "The specular exponent controls the size of highlights: high values give\nsmall, sharp highlights, low values broad and soft ones.",
+    "Scene files can define several cameras. Pick one with --camera NAME\ninstead of editing the file.",
+    "Render a small preview first with --width 320 --height 180,\nthen go back to full resolution.",
+    "Turning off features with --no-shadows or --no-reflections is a quick way\nto see what each one contributes to the final image.",
+    "A plane is infinite, so a huge sphere around the scene is an easy way\nto add walls that every ray eventually hits.",
+    "Spotlights only light what falls within their angle. Aim them with\ntarget and widen the angle if an object stays dark.",
+];
+
+// Pick the tip for the current day, so it changes once per day.
+fn tip_of_the_day() -> &'static str {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400);
+    TIPS[days as usize % TIPS.len()]
+}
 
 pub fn progress_bar(rx: Receiver<Duration>) {
     let total_duration = 10; // 15 seconds to complete
@@ -13,9 +36,7 @@ pub fn progress_bar(rx: Receiver<Duration>) {
     print!("{}[2J", 27 as char);
 
     println!("============== Raytracing tip of the day ==============");
-    //read content of answer.txt and print it
-    let contents = std::fs::read_to_string("answer.txt").expect("Something went wrong reading the file");
-    println!("{}", contents);
+    println!("{}", tip_of_the_day());
     println!("========================================================");
     println!("Rendering started. Please wait...");
 
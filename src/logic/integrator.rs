@@ -0,0 +1,363 @@
+use crate::logic::ray::{fresnel, Intersection};
+use crate::logic::Color;
+use crate::logic::Environment;
+use crate::logic::Light;
+use crate::logic::Options;
+use crate::logic::Ray;
+use crate::logic::Rng;
+use crate::logic::Vec3;
+use crate::logic::World;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+// Number of rays along a path after which paths carrying little light may be
+// ended at random (Russian roulette).
+const ROULETTE_DEPTH: u8 = 3;
+
+// Define how the light seen along a camera ray is computed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    Whitted, // Direct light, ambient light and perfect reflections, as a fast preview
+    Path,    // Monte Carlo path tracing, adding the light bounced between surfaces
+}
+
+impl Integrator {
+    // Return the color seen along a camera ray, or the color of the
+    // environment if the ray leaves the scene. The path tracer draws its
+    // random choices from 'rng'.
+    pub fn radiance(
+        &self,
+        ray: Ray,
+        world: &World,
+        lights: &[Light],
+        environment: &Environment,
+        options: &Options,
+        rng: &mut Rng,
+    ) -> Color {
+        match self {
+            // Without a single ray allowed ('max_rays' 0), the camera sees
+            // the environment, as it would through a scene without objects.
+            Integrator::Whitted => Ray::cast_ray(ray, world, lights, environment, options, 0, rng)
+                .unwrap_or_else(|| environment.color(ray.direction)),
+            Integrator::Path => trace_path(ray, world, lights, environment, options, rng),
+        }
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Integrator, String> {
+        match text {
+            "whitted" => Ok(Integrator::Whitted),
+            "path" => Ok(Integrator::Path),
+            _ => Err("expected whitted or path".to_string()),
+        }
+    }
+}
+
+// Follow one random path of light backwards from the origin of the ray, and
+// return the light it carries. At every surface the light arriving straight
+// from each light source is added, then the path goes on in a direction
+// chosen from the material. Paths end when they leave the scene, which lights
+// them with the color of the environment, after 'max_rays' rays, or at random
+// once they carry little light. Emissive materials add their light wherever a
+// path meets them, so they light the objects around them.
+//
+// Ambient lights are ignored: the light bounced between surfaces and coming
+// from the environment takes their place. Skies and environment maps are
+// lights of their own, sampled at every surface like the light sources.
+fn trace_path(
+    ray: Ray,
+    world: &World,
+    lights: &[Light],
+    environment: &Environment,
+    options: &Options,
+    rng: &mut Rng,
+) -> Color {
+    let mut color = Color::black();
+    let mut throughput = Color::white(); // Share of the light found reaching the camera
+    let mut ray = ray;
+    let mut sharp = true; // Whether the ray may see area lights
+
+    for depth in 0..options.max_rays {
+        // Area lights are seen by camera rays and in mirrors. Rays scattered
+        // by other surfaces skip them, as their light was added already.
+        let intersection = Ray::intersect(ray, world, crate::EPSILON, f64::INFINITY);
+        if sharp {
+            let reach = intersection
+                .as_ref()
+                .map_or(f64::INFINITY, |hit| hit.distance);
+            if let Some(glow) = Light::glow(lights, ray, reach) {
+                return color + throughput * glow;
+            }
+        }
+        // Environments sampled as lights were added at the last surface
+        // already, like area lights.
+        let Some(intersection) = intersection else {
+            if sharp || !environment.is_light() {
+                color += throughput * environment.color(ray.direction);
+            }
+            return color;
+        };
+        // Glowing materials light the scene through the paths reaching them.
+        color += throughput * intersection.material.emitted();
+
+        let surface = Scattering::new(&intersection, ray.direction, options);
+        color += throughput * surface.direct_light(world, lights, environment, options, rng);
+
+        let Some(bounce) = surface.sample(rng) else {
+            break; // The surface absorbed the light.
+        };
+        throughput = throughput * bounce.weight;
+        ray = bounce.ray;
+        sharp = bounce.sharp;
+
+        // Keep paths in proportion to the light they carry, making up for the
+        // ones ended by strengthening the others.
+        if depth + 1 >= ROULETTE_DEPTH {
+            let (r, g, b) = throughput.components();
+            let survival = r.max(g).max(b).min(0.95);
+            if rng.next_f64() >= survival {
+                break;
+            }
+            throughput = throughput * survival.recip();
+        }
+    }
+
+    color
+}
+
+// Describe how a surface point scatters light, splitting the light arriving
+// there into shares scattered in different ways. The shares add up to at most
+// 1, the rest being absorbed.
+struct Scattering<'a> {
+    intersection: &'a Intersection,
+    direction: Vec3, // Direction of the ray arriving at the point
+    normal: Vec3,    // Shading normal, on the side the ray came from
+    surface: Vec3,   // Normal of the surface itself, on the side the ray came from
+    diffuse: f64,    // Share scattered evenly in all directions
+    glossy: f64,     // Share scattered around the mirror direction
+    mirror: f64,     // Share reflected as by a mirror
+    refracted: f64,  // Share passing through the surface
+    refraction: Vec3,
+}
+
+impl<'a> Scattering<'a> {
+    // Split the light following the material. Transparent materials let
+    // through what the Fresnel effect does not reflect, and the opaque rest of
+    // the material scatters its 'diffuse' share evenly and up to the 'specular'
+    // share around the mirror direction, both tinted by its color.
+    fn new(intersection: &'a Intersection, direction: Vec3, options: &Options) -> Scattering<'a> {
+        let material = &intersection.material;
+        let (normal, surface) = if intersection.front_face {
+            (intersection.normal, intersection.geometric_normal)
+        } else {
+            (-intersection.normal, -intersection.geometric_normal)
+        };
+
+        let mut mirror = material.reflectiveness;
+        let mut refracted = 0.0;
+        let mut refraction = Vec3::zero();
+        if material.transparency > 0.0 && options.refractions {
+            let eta = if intersection.front_face {
+                material.index_of_refraction.recip()
+            } else {
+                material.index_of_refraction
+            };
+
+            match direction.refract(normal, eta) {
+                // Light that cannot leave the material is reflected back.
+                None => mirror += material.transparency,
+                Some(bent) => {
+                    let reflected = fresnel(-direction.dot(normal), eta);
+                    mirror += material.transparency * reflected;
+                    refracted = material.transparency * (1.0 - reflected);
+                    refraction = bent.normalize();
+                }
+            }
+        }
+        if !options.reflections {
+            mirror = 0.0;
+        }
+
+        let opaque = (1.0 - material.reflectiveness - material.transparency).max(0.0);
+        let diffuse = material.diffuse.clamp(0.0, 1.0);
+        let glossy = material.specular.clamp(0.0, 1.0 - diffuse);
+        let diffuse = if options.diffuse { diffuse } else { 0.0 };
+        let glossy = if options.specular { glossy } else { 0.0 };
+
+        Scattering {
+            intersection,
+            direction,
+            normal,
+            surface,
+            diffuse: opaque * diffuse,
+            glossy: opaque * glossy,
+            mirror,
+            refracted,
+            refraction,
+        }
+    }
+
+    // Return the share of the light arriving from 'incoming' scattered back
+    // along the ray, per unit of solid angle: a Lambertian term for the
+    // diffuse share and a normalized Phong lobe for the glossy one.
+    fn evaluate(&self, incoming: Vec3) -> Color {
+        let exponent = self.intersection.material.specular_exponent.max(0.0);
+        let mirrored = self.direction.reflect(self.normal);
+        let lobe = incoming.dot(mirrored).max(0.0).powf(exponent);
+        let share = self.diffuse / PI + self.glossy * (exponent + 2.0) / (2.0 * PI) * lobe;
+        self.intersection.material.color * share
+    }
+
+    // Return the light from the light sources scattered back along the ray.
+    // A light of intensity 1 lights a surface facing it like the Whitted
+    // integrator does, so a matt surface looks the same with both. An
+    // environment lighting the scene sends light from directions chosen at
+    // random, more often where it is brighter.
+    fn direct_light(
+        &self,
+        world: &World,
+        lights: &[Light],
+        environment: &Environment,
+        options: &Options,
+        rng: &mut Rng,
+    ) -> Color {
+        let mut color = Color::black();
+        if self.diffuse + self.glossy <= 0.0 {
+            return color;
+        }
+
+        let point = self.intersection.hit_point;
+        for light in lights {
+            for (incoming, arriving) in light.arriving(world, options, point, self.surface, rng) {
+                let cosine = incoming.dot(self.normal);
+                if cosine <= 0.0 || incoming.dot(self.surface) <= 0.0 {
+                    continue; // The light is behind the surface.
+                }
+                // The intensity is the light falling on a surface facing the
+                // light, see Light::glow. PI makes up for the 1 / PI of even
+                // scattering, so surfaces are lit as with the Whitted renderer.
+                color += self.evaluate(incoming) * arriving * (PI * light.intensity * cosine);
+            }
+        }
+
+        for (incoming, arriving, pdf) in environment.samples(rng) {
+            let cosine = incoming.dot(self.normal);
+            if cosine <= 0.0 || incoming.dot(self.surface) <= 0.0 {
+                continue;
+            }
+            let ray = Ray {
+                origin: point.correct(self.surface),
+                direction: incoming,
+            };
+            let transmission = if options.shadows {
+                Light::transmission(world, options, ray, f64::INFINITY)
+            } else {
+                Color::white()
+            };
+            color += self.evaluate(incoming) * arriving * transmission * (cosine / pdf);
+        }
+        color
+    }
+
+    // Choose at random how the light is scattered, in proportion to the
+    // shares, and return how the path goes on. Returns None when the light is
+    // absorbed.
+    fn sample(&self, rng: &mut Rng) -> Option<Bounce> {
+        let total = self.diffuse + self.glossy + self.mirror + self.refracted;
+        if total <= 0.0 {
+            return None;
+        }
+        let material = &self.intersection.material;
+        let point = self.intersection.hit_point;
+        let mut choice = rng.next_f64() * total;
+
+        // Light passing through keeps the color it has inside the material,
+        // and takes on its color going in.
+        if choice < self.refracted {
+            let tint = if self.intersection.front_face {
+                material.color
+            } else {
+                Color::white()
+            };
+            let ray = Ray {
+                origin: point.correct(-self.surface),
+                direction: self.refraction,
+            };
+            return Some(Bounce {
+                ray,
+                weight: tint * total,
+                sharp: true,
+            });
+        }
+        choice -= self.refracted;
+
+        let sharp = choice < self.mirror;
+        let (direction, weight) = if sharp {
+            (self.direction.reflect(self.normal), Color::white())
+        } else if choice < self.mirror + self.diffuse {
+            // Choosing directions more often where they light the surface
+            // more cancels out the cosine of the angle to the normal.
+            let direction = around(self.normal, cosine_lobe(rng));
+            (direction, material.color)
+        } else {
+            let exponent = material.specular_exponent.max(0.0);
+            let mirrored = self.direction.reflect(self.normal);
+            let direction = around(mirrored, phong_lobe(exponent, rng));
+            let cosine = direction.dot(self.normal);
+            if cosine <= 0.0 {
+                return None;
+            }
+            let factor = (exponent + 2.0) / (exponent + 1.0) * cosine;
+            (direction, material.color * factor)
+        };
+
+        // Directions into the surface are absorbed.
+        if direction.dot(self.surface) <= 0.0 {
+            return None;
+        }
+        let ray = Ray {
+            origin: point.correct(self.surface),
+            direction,
+        };
+        Some(Bounce {
+            ray,
+            weight: weight * total,
+            sharp,
+        })
+    }
+}
+
+// Describe how a path goes on from a surface.
+struct Bounce {
+    ray: Ray,      // Ray the path goes on with
+    weight: Color, // Factor the light found along the ray is multiplied by
+    sharp: bool,   // Whether the ray is a mirror reflection or a refraction
+}
+
+// Return a random direction around the z axis, more likely the closer it is
+// to the axis, in proportion to the cosine of the angle to it.
+fn cosine_lobe(rng: &mut Rng) -> Vec3 {
+    let (u, v) = (rng.next_f64(), rng.next_f64());
+    let radius = u.sqrt();
+    let angle = 2.0 * PI * v;
+    Vec3::new(radius * angle.cos(), radius * angle.sin(), (1.0 - u).sqrt())
+}
+
+// Return a random direction around the z axis, in proportion to the cosine of
+// the angle to it raised to 'exponent'.
+fn phong_lobe(exponent: f64, rng: &mut Rng) -> Vec3 {
+    let (u, v) = (rng.next_f64(), rng.next_f64());
+    let cosine = u.powf((exponent + 1.0).recip());
+    let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+    let angle = 2.0 * PI * v;
+    Vec3::new(sine * angle.cos(), sine * angle.sin(), cosine)
+}
+
+// Turn a direction given around the z axis to be around 'axis' instead.
+fn around(axis: Vec3, local: Vec3) -> Vec3 {
+    let (first, second) = axis.basis();
+    first * local.x + second * local.y + axis * local.z
+}
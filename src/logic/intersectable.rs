@@ -1,19 +1,113 @@
+use crate::logic::Aabb;
 use crate::logic::Material;
 use crate::logic::Ray;
 use crate::logic::Vec3;
 use std::fmt::Debug;
+use std::sync::Arc;
+
+// Define what is known about the point where a ray hits an object.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub distance: f64,          // Distance along the ray to the hit
+    pub point: Vec3,            // Coordinates of the hit point
+    pub local_point: Vec3,      // Coordinates of the hit point before any transform
+    pub geometric_normal: Vec3, // Unit normal of the surface itself, pointing out
+    pub normal: Vec3,           // Unit normal used for shading, pointing out
+    pub front_face: bool,       // Whether the ray hit the object from the outside
+    pub uv: (f64, f64),         // Texture coordinates of the hit point
+    pub dpdu: Vec3,             // How the hit point moves as u grows
+    pub dpdv: Vec3,             // How the hit point moves as v grows
+    pub local_dpdu: Vec3,       // The same before any transform
+    pub local_dpdv: Vec3,       // The same before any transform
+    pub object: usize,          // Index of the object in the scene
+}
+
+impl Hit {
+    // Create the hit 'distance' along the ray, on a surface with the given
+    // outward normal. The ray comes from outside when it travels against the
+    // normal.
+    pub fn new(ray: Ray, distance: f64, normal: Vec3, uv: (f64, f64)) -> Hit {
+        let point = ray.origin + (ray.direction * distance);
+        Hit {
+            distance,
+            point,
+            local_point: point,
+            geometric_normal: normal,
+            normal,
+            front_face: ray.direction.dot(normal) < 0.0,
+            uv,
+            dpdu: Vec3::splat(0.0),
+            dpdv: Vec3::splat(0.0),
+            local_dpdu: Vec3::splat(0.0),
+            local_dpdv: Vec3::splat(0.0),
+            object: 0,
+        }
+    }
+
+    // Shade the hit with another normal, such as one interpolated between
+    // the vertices of a mesh, keeping the geometric one.
+    pub fn with_shading_normal(mut self, normal: Vec3) -> Hit {
+        self.normal = normal;
+        self
+    }
+
+    // Give the derivatives of the hit point with respect to the texture
+    // coordinates, which orient bump and normal maps on the surface. They
+    // should point such that dpdu × dpdv points out of the object; hits
+    // without them are shaded with an arbitrary orientation.
+    pub fn with_derivatives(mut self, dpdu: Vec3, dpdv: Vec3) -> Hit {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self.local_dpdu = dpdu;
+        self.local_dpdv = dpdv;
+        self
+    }
+}
 
 // Define a trait named 'Intersectable' that represents objects which can be
-// intersected by rays in a 3D environment.
-pub trait Intersectable: Debug {
-    // This method calculates the intersection of the object with a ray
-    // and returns the distance to the intersection point (if any).
-    fn intersect(&self, ray: Ray) -> Option<f64>;
+// intersected by rays in a 3D environment. Objects are shared between the
+// rendering threads, so they must be Send and Sync.
+pub trait Intersectable: Debug + Send + Sync {
+    // This method calculates the closest intersection of the object with a
+    // ray farther than 't_min' and no farther than 't_max' along it, and
+    // returns what is known about the hit point (if any).
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit>;
 
     // This method returns the material properties of the object.
-    fn material(&self) -> Material;
+    fn material(&self) -> &Material;
+
+    // This method returns a box enclosing the whole object, used to skip
+    // objects a ray cannot hit. Unbounded objects such as planes return None.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+// Let boxed and shared objects be used wherever an object is expected, for
+// example to transform an object known only as a trait object, or to place
+// one mesh in the scene several times through an 'Arc'.
+impl<T: Intersectable + ?Sized> Intersectable for Box<T> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        (**self).intersect(ray, t_min, t_max)
+    }
+
+    fn material(&self) -> &Material {
+        (**self).material()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+impl<T: Intersectable + ?Sized> Intersectable for Arc<T> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        (**self).intersect(ray, t_min, t_max)
+    }
+
+    fn material(&self) -> &Material {
+        (**self).material()
+    }
 
-    // This method computes the normal vector at a given intersection point
-    // on the object, which is useful for lighting and shading calculations.
-    fn normal(&self, hit_point: Vec3) -> Vec3;
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
 }
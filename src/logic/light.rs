@@ -1,8 +1,11 @@
 use crate::logic::Color;
-use crate::logic::Intersectable;
 use crate::logic::Options;
 use crate::logic::ray::{Intersection, Ray};
+use crate::logic::Rng;
+use crate::logic::Sampler;
 use crate::logic::Vec3;
+use crate::logic::World;
+use std::f64::consts::PI;
 
 // Define a struct for representing a light source.
 #[derive(Debug)]
@@ -11,6 +14,7 @@ pub struct Light {
     pub position: Vec3,
     pub intensity: f64,
     pub color: Color,
+    pub attenuation: Attenuation,
 }
 
 // Define an enum to represent different types of lights.
@@ -18,19 +22,145 @@ pub struct Light {
 pub enum LightType {
     Ambient,
     Point,
-    Spotlight { direction: Vec3, angle: f64 },
+    // A light shining within a cone around 'direction'. Its light fades out
+    // between 'inner_angle' and 'angle', both measured from the axis in
+    // radians; equal angles give a hard edge.
+    Spotlight { direction: Vec3, angle: f64, inner_angle: f64 },
+    // A light with a size, centered on the position, sending 'samples'
+    // shadow rays to points spread over its shape. Its intensity is split
+    // between the rays, so it lights the scene as much as a point light of
+    // the same intensity would, whatever its size.
+    Area { shape: AreaShape, samples: u32 },
+    // A light so far away that its rays are parallel, such as the sun,
+    // shining along 'direction'. A light with an angular diameter (in
+    // radians) is a disk in the sky, sending 'samples' shadow rays across it.
+    Directional { direction: Vec3, diameter: f64, samples: u32 },
+}
+
+// Define the shapes of area lights. They give off light on both sides.
+#[derive(Debug)]
+pub enum AreaShape {
+    Rectangle { u: Vec3, v: Vec3 }, // Two sides of the rectangle, as vectors
+    Disk { normal: Vec3, radius: f64 },
+    Sphere { radius: f64 },
+}
+
+// Define how the light of a light fades with the distance to it. Directional
+// and ambient lights are not attenuated, as they have no position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attenuation {
+    None,          // The light does not fade
+    Linear,        // The light fades as the distance grows
+    InverseSquare, // The light fades as the square of the distance grows, like real lights
+    // The light is divided by constant + linear * d + quadratic * d^2.
+    Custom { constant: f64, linear: f64, quadratic: f64 },
+}
+
+impl Attenuation {
+    // Return the share of the light left at 'distance' from the light.
+    pub fn factor(&self, distance: f64) -> f64 {
+        let divisor = match *self {
+            Attenuation::None => return 1.0,
+            Attenuation::Linear => distance,
+            Attenuation::InverseSquare => distance * distance,
+            Attenuation::Custom { constant, linear, quadratic } => {
+                constant + linear * distance + quadratic * distance * distance
+            }
+        };
+        // Keep points right next to the light from getting infinitely bright.
+        divisor.max(crate::EPSILON).recip()
+    }
+}
+
+impl AreaShape {
+    // Return the point of the shape centered on 'center' matching (s, t) in
+    // the unit square. Spheres only use the half facing 'towards', as the
+    // other half cannot be seen from there.
+    fn point(&self, center: Vec3, towards: Vec3, s: f64, t: f64) -> Vec3 {
+        match self {
+            AreaShape::Rectangle { u, v } => center + *u * (s - 0.5) + *v * (t - 0.5),
+            AreaShape::Disk { normal, radius } => {
+                let (first, second) = normal.normalize().basis();
+                let (distance, angle) = (radius * s.sqrt(), 2.0 * PI * t);
+                center + first * (distance * angle.cos()) + second * (distance * angle.sin())
+            }
+            AreaShape::Sphere { radius } => {
+                let axis = (towards - center).normalize();
+                let (first, second) = axis.basis();
+                let (height, angle) = (s, 2.0 * PI * t);
+                let ring = (1.0 - height * height).max(0.0).sqrt();
+                let direction = first * (ring * angle.cos())
+                    + second * (ring * angle.sin())
+                    + axis * height;
+                center + direction * *radius
+            }
+        }
+    }
+
+    // Return the distance along the ray to the shape centered on 'center',
+    // if the ray meets it farther than EPSILON and no farther than 't_max'.
+    fn intersect(&self, center: Vec3, ray: Ray, t_max: f64) -> Option<f64> {
+        let distance = match self {
+            AreaShape::Rectangle { u, v } => {
+                let normal = u.cross(*v);
+                let t = (center - ray.origin).dot(normal) / ray.direction.dot(normal);
+                // Express the hit point in terms of the sides, which works for
+                // any parallelogram.
+                let offset = ray.origin + ray.direction * t - center;
+                let area = normal.dot(normal);
+                let a = offset.cross(*v).dot(normal) / area;
+                let b = u.cross(offset).dot(normal) / area;
+                if a.abs() > 0.5 || b.abs() > 0.5 {
+                    return None;
+                }
+                t
+            }
+            AreaShape::Disk { normal, radius } => {
+                let t = (center - ray.origin).dot(*normal) / ray.direction.dot(*normal);
+                let offset = ray.origin + ray.direction * t - center;
+                if offset.length() > *radius {
+                    return None;
+                }
+                t
+            }
+            AreaShape::Sphere { radius } => {
+                let oc = ray.origin - center;
+                let b = oc.dot(ray.direction);
+                let c = oc.dot(oc) - radius * radius;
+                let discriminant = b * b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let near = -b - discriminant.sqrt();
+                if near > crate::EPSILON {
+                    near
+                } else {
+                    -b + discriminant.sqrt()
+                }
+            }
+        };
+
+        // Rays parallel to flat shapes give an infinite or undefined distance,
+        // which fails this test.
+        if distance > crate::EPSILON && distance <= t_max {
+            Some(distance)
+        } else {
+            None
+        }
+    }
 }
 
 impl Light {
     // Function to calculate the shading of an object at an intersection point.
     pub fn shade(
-        objects: &[Box<dyn Intersectable>],
+        world: &World,
         lights: &[Light],
         options: &Options,
-        intersection: Intersection,
+        intersection: &Intersection,
         direction: Vec3,
+        rng: &mut Rng,
     ) -> Color {
-        let mat = intersection.material;
+        let mat = &intersection.material;
         let mut diff_light = Color::black(); // Initialize diffuse light.
         let mut spec_light = Color::black(); // Initialize specular light.
 
@@ -42,46 +172,19 @@ impl Light {
                     diff_light += light.color * light.intensity;
                 }
 
-                LightType::Point | LightType::Spotlight { .. } => {
-                    let light_vec = light.position - intersection.hit_point;
-                    let light_dir = light_vec.normalize();
-                    let light_dis = light_vec.length();
-                    let light_angle = light_dir.dot(intersection.normal);
-
-                    // Create a ray from the intersection point to the light source.
-                    let light_ray = Ray {
-                        origin: if light_angle < 0.0 {
-                            intersection.hit_point.correct(-intersection.normal)
-                        } else {
-                            intersection.hit_point.correct(intersection.normal)
-                        },
-                        direction: light_dir,
-                    };
-
-                    // Check if the intersection point is in shadow.
-                    let hit_by_light = match Ray::intersect(light_ray, objects) {
-                        None => true,
-                        Some(intersect) => light_dis <= intersect.distance,
-                    };
-
-                    if hit_by_light || !options.shadows {
-                        if let LightType::Spotlight { direction, angle } = &light.light_type {
-                            let spotlight_dir = direction.normalize();
-                            let cos_theta = light_dir.dot(spotlight_dir);
-
-                            // Check if the light falls within the spotlight's angle.
-                            if cos_theta < angle.cos() {
-                                continue; // Skip this light source.
-                            }
-                        }
+                _ => {
+                    let point = intersection.hit_point;
+                    let surface = intersection.geometric_normal;
+                    for (light_dir, color) in light.arriving(world, options, point, surface, rng) {
+                        let light_angle = light_dir.dot(intersection.normal);
 
                         // Calculate the reflection of light.
                         let light_reflection = (-light_dir).reflect(intersection.normal);
                         let angle = -(light_reflection.dot(direction));
 
                         // Add to diffuse and specular light based on angles.
-                        diff_light += light.color * (light.intensity * light_angle.max(0.0));
-                        spec_light += light.color * angle.max(0.0).powf(mat.specular_exponent);
+                        diff_light += color * (light.intensity * light_angle.max(0.0));
+                        spec_light += color * angle.max(0.0).powf(mat.specular_exponent);
                     }
                 }
             }
@@ -103,17 +206,208 @@ impl Light {
 
         mat.color * factor // Return the final shaded color.
     }
+
+    // Return the light reaching 'point' from this light, as the direction of
+    // each shadow ray towards the light along with the color of the light
+    // arriving along it: the light's color, dimmed and tinted by the objects
+    // in between, and split evenly between the rays of area lights. 'surface'
+    // is the normal of the surface at the point, whichever way it faces.
+    // Ambient lights send no rays, and rays no light comes along are left
+    // out.
+    pub fn arriving(
+        &self,
+        world: &World,
+        options: &Options,
+        point: Vec3,
+        surface: Vec3,
+        rng: &mut Rng,
+    ) -> Vec<(Vec3, Color)> {
+        // Find the direction and the distance of each shadow ray.
+        let towards = |position: Vec3| {
+            let light_vec = position - point;
+            (light_vec.normalize(), light_vec.length())
+        };
+        let rays: Vec<(Vec3, f64)> = match &self.light_type {
+            LightType::Ambient => return Vec::new(),
+            LightType::Area { shape, samples } => Sampler::Jittered
+                .samples(*samples, rng)
+                .into_iter()
+                .map(|(s, t)| towards(shape.point(self.position, point, s, t)))
+                .collect(),
+            LightType::Directional {
+                direction,
+                diameter,
+                samples,
+            } => {
+                let axis = -direction.normalize();
+                if *diameter > 0.0 {
+                    Sampler::Jittered
+                        .samples(*samples, rng)
+                        .into_iter()
+                        .map(|(s, t)| (cone(axis, diameter / 2.0, s, t), f64::INFINITY))
+                        .collect()
+                } else {
+                    vec![(axis, f64::INFINITY)]
+                }
+            }
+            _ => vec![towards(self.position)],
+        };
+
+        let share = (rays.len() as f64).recip();
+        rays.into_iter()
+            .filter_map(|(light_dir, light_dis)| {
+                self.arriving_along(world, options, point, surface, light_dir, light_dis)
+            })
+            .map(|(light_dir, color)| (light_dir, color * share))
+            .collect()
+    }
+
+    // Return the color of the light reaching 'point' along the direction
+    // 'light_dir' from a point of the light 'light_dis' away.
+    fn arriving_along(
+        &self,
+        world: &World,
+        options: &Options,
+        point: Vec3,
+        surface: Vec3,
+        light_dir: Vec3,
+        light_dis: f64,
+    ) -> Option<(Vec3, Color)> {
+
+        // Create a ray from the point to the light source, starting off the
+        // side of the surface facing the light.
+        let light_ray = Ray {
+            origin: if light_dir.dot(surface) < 0.0 {
+                point.correct(-surface)
+            } else {
+                point.correct(surface)
+            },
+            direction: light_dir,
+        };
+
+        // Check how much of the light reaches the point.
+        let transmission = if options.shadows {
+            Light::transmission(world, options, light_ray, light_dis)
+        } else {
+            Color::white()
+        };
+        if transmission == Color::black() {
+            return None;
+        }
+
+        // Check if the light falls within the spotlight's angle, fading it
+        // out smoothly between the inner and the outer cone.
+        let mut factor = 1.0;
+        if let LightType::Spotlight { direction, angle, inner_angle } = &self.light_type {
+            let cos_theta = light_dir.dot(direction.normalize());
+            let (outer, inner) = (angle.cos(), inner_angle.cos());
+            if cos_theta < outer {
+                return None;
+            }
+            if cos_theta < inner {
+                let t = (cos_theta - outer) / (inner - outer);
+                factor = t * t * (3.0 - 2.0 * t);
+            }
+        }
+
+        // Lights infinitely far away have no position to fade from.
+        if light_dis.is_finite() {
+            factor *= self.attenuation.factor(light_dis);
+        }
+
+        Some((light_dir, self.color * transmission * factor))
+    }
+
+    // Return the color of the closest area light seen along the ray, its
+    // color times its intensity, if one is nearer than 't_max'. Lights do not
+    // block the light of other lights, so only rays looking for what they
+    // see meet them.
+    //
+    // Lights are described by the light they cast rather than by the
+    // brightness of their surface: the light falling on a surface facing a
+    // light is its intensity, dimmed only by its attenuation. So what is seen
+    // of an area light does not follow from the light it casts, as it would
+    // for a real one, whose light grows with its area and falls with the
+    // square of the distance. It looks as bright whatever its size and
+    // distance, and a larger light only softens shadows. Emissive materials,
+    // which light the scene through the paths reaching them, look as bright
+    // as the light they cast.
+    pub fn glow(lights: &[Light], ray: Ray, t_max: f64) -> Option<Color> {
+        let mut closest = None;
+        let mut reach = t_max;
+        for light in lights {
+            if let LightType::Area { shape, .. } = &light.light_type {
+                if let Some(distance) = shape.intersect(light.position, ray, reach) {
+                    reach = distance;
+                    closest = Some(light.color * light.intensity);
+                }
+            }
+        }
+        closest
+    }
+
+    // Return the share of the light reaching the origin of the ray from a light
+    // 'distance' away along it. Opaque objects in between block the light,
+    // while transparent ones let part of it through, tinted by their color.
+    pub(crate) fn transmission(world: &World, options: &Options, ray: Ray, distance: f64) -> Color {
+        let mut transmission = Color::white();
+        let mut ray = ray;
+        let mut remaining = distance;
+
+        while let Some(intersect) = Ray::intersect(ray, world, crate::EPSILON, remaining) {
+            if remaining <= intersect.distance {
+                break; // The light is in front of the object
+            }
+
+            let material = &intersect.material;
+            if material.transparency <= 0.0 || !options.refractions {
+                return Color::black();
+            }
+            transmission = transmission * material.transparency;
+            if intersect.front_face {
+                transmission = transmission * material.color;
+            }
+
+            // Carry on from just past the surface.
+            ray.origin = intersect.hit_point.correct(ray.direction);
+            remaining -= intersect.distance + crate::EPSILON;
+        }
+
+        transmission
+    }
+}
+
+// Return the direction around 'axis', at most 'half_angle' radians away from
+// it, matching (s, t) in the unit square. Directions are spread evenly over
+// the cone.
+pub(crate) fn cone(axis: Vec3, half_angle: f64, s: f64, t: f64) -> Vec3 {
+    let (first, second) = axis.basis();
+    let height = 1.0 - s * (1.0 - half_angle.cos());
+    let ring = (1.0 - height * height).max(0.0).sqrt();
+    let angle = 2.0 * PI * t;
+    first * (ring * angle.cos()) + second * (ring * angle.sin()) + axis * height
 }
 
-// Function to create a spotlight.
-pub fn create_spotlight(position: Vec3, direction: Vec3, intensity: f64, color: Color, angle: f64) -> Light {
+// Function to create a spotlight. The angles are in degrees, and the light
+// fades out between 'inner_angle' and 'angle'.
+pub fn create_spotlight(
+    position: Vec3,
+    direction: Vec3,
+    intensity: f64,
+    color: Color,
+    angle: f64,
+    inner_angle: f64,
+    attenuation: Attenuation,
+) -> Light {
     Light {
         light_type: LightType::Spotlight { 
             direction: (position - direction).normalize(), 
-            angle: angle.to_radians() 
+            angle: angle.to_radians(),
+            inner_angle: inner_angle.min(angle).to_radians(),
         },
-        position: position,
-        intensity: intensity,  
-        color: color,
+        position,
+        intensity,
+        color,
+        attenuation,
     }
 }
@@ -1,27 +1,150 @@
 // Import the 'Color' type from the 'super::color' module.
 use crate::logic::Color;
+use crate::logic::Hit;
+use crate::logic::Texture;
+use crate::logic::Vec3;
+use std::sync::Arc;
 
 // Define a struct representing material properties.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Material {
-    pub color: Color,           // The color of the material
-    pub diffuse: f64,           // Diffuse reflectivity (0.0 to 1.0)
-    pub specular: f64,          // Specular reflectivity (0.0 to 1.0)
-    pub specular_exponent: f64, // Specular exponent for shininess
-    pub reflectiveness: f64,    // Reflectiveness (0.0 to 1.0)
+    pub color: Color,             // The color of the material
+    pub diffuse: f64,             // Diffuse reflectivity (0.0 to 1.0)
+    pub specular: f64,            // Specular reflectivity (0.0 to 1.0)
+    pub specular_exponent: f64,   // Specular exponent for shininess
+    pub reflectiveness: f64,      // Reflectiveness (0.0 to 1.0)
+    pub transparency: f64,        // Share of light passing through (0.0 to 1.0)
+    pub index_of_refraction: f64, // How much light bends entering the material
+    pub emission: Color,          // Color of the light given off by the material
+    pub emission_strength: f64,   // Factor the emitted light is multiplied by
+    pub textures: Textures,       // Textures replacing some of the values above
 }
 
+// Define the textures of a material. Each one replaces the matching value of
+// the material, varying it over the surface. Textures standing for a single
+// number use the average of their color.
+//
+// The last two change the shading normal instead. The bump map gives the
+// height of the surface above its true position, and the normal map gives
+// the normal itself, its red, green and blue components going from -1 (at 0)
+// to 1 (at 1) along u, v and the outward normal.
+#[derive(Clone, Debug, Default)]
+pub struct Textures {
+    pub color: Option<Arc<dyn Texture>>,
+    pub diffuse: Option<Arc<dyn Texture>>,
+    pub specular: Option<Arc<dyn Texture>>,
+    pub reflectiveness: Option<Arc<dyn Texture>>,
+    pub transparency: Option<Arc<dyn Texture>>,
+    pub emission: Option<Arc<dyn Texture>>,
+    pub bump: Option<Arc<dyn Texture>>,
+    pub normal: Option<Arc<dyn Texture>>,
+}
+
+// Step in texture coordinates over which the slope of a bump map is measured.
+const BUMP_STEP: f64 = 1e-3;
+
 impl Material {
-    // Create a new Material with neutral properties (all zeros).
+    // Create a new Material with neutral properties (all zeros, and an index
+    // of refraction of 1 so light would pass through unbent).
     pub fn neutral() -> Material {
         Material {
-            color: Color::black(),  // Black color
-            diffuse: 0.0,           // No diffuse reflection
-            specular: 0.0,          // No specular reflection
-            specular_exponent: 0.0, // Specular exponent is zero
-            reflectiveness: 0.0,    // No reflectiveness
+            color: Color::black(),         // Black color
+            diffuse: 0.0,                  // No diffuse reflection
+            specular: 0.0,                 // No specular reflection
+            specular_exponent: 0.0,        // Specular exponent is zero
+            reflectiveness: 0.0,           // No reflectiveness
+            transparency: 0.0,             // Opaque
+            index_of_refraction: 1.0,      // Same as air, light goes straight through
+            emission: Color::black(),      // No light given off
+            emission_strength: 1.0,        // Emitted light as bright as its color
+            textures: Textures::default(), // No textures
         }
     }
+
+    // Return the material at a point of a surface, given by its texture
+    // coordinates and its position in the object's own space, with the
+    // textures replaced by their values there.
+    pub fn at(&self, uv: (f64, f64), point: Vec3) -> Material {
+        let textures = &self.textures;
+        let number = |texture: &Option<Arc<dyn Texture>>, value: f64| match texture {
+            Some(texture) => texture.color(uv, point).average().clamp(0.0, 1.0),
+            None => value,
+        };
+        // Textured materials let through at most what they do not reflect.
+        let reflectiveness = number(&textures.reflectiveness, self.reflectiveness);
+
+        Material {
+            color: match &textures.color {
+                Some(texture) => texture.color(uv, point),
+                None => self.color,
+            },
+            diffuse: number(&textures.diffuse, self.diffuse),
+            specular: number(&textures.specular, self.specular),
+            specular_exponent: self.specular_exponent,
+            reflectiveness,
+            transparency: number(&textures.transparency, self.transparency)
+                .min(1.0 - reflectiveness),
+            index_of_refraction: self.index_of_refraction,
+            emission: match &textures.emission {
+                Some(texture) => texture.color(uv, point),
+                None => self.emission,
+            },
+            emission_strength: self.emission_strength,
+            textures: Textures::default(),
+        }
+    }
+
+    // Return the light the material gives off by itself, the same in every
+    // direction and on both sides of the surface.
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    // Return the outward normal to shade a hit with: the normal of the hit,
+    // turned by the normal map and then tilted by the slope of the bump map.
+    // Maps are laid along the derivatives of the hit, so they have no effect
+    // on the bump map of a hit without them.
+    pub fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        let mut normal = hit.normal;
+
+        if let Some(map) = &self.textures.normal {
+            let (tangent, bitangent) = tangent_frame(hit, normal);
+            let (x, y, z) = map.color(hit.uv, hit.local_point).components();
+            let turned =
+                tangent * (2.0 * x - 1.0) + bitangent * (2.0 * y - 1.0) + normal * (2.0 * z - 1.0);
+            if turned.length() > crate::EPSILON {
+                normal = turned.normalize();
+            }
+        }
+
+        if let Some(bump) = &self.textures.bump {
+            // Step along the surface in u and in v, and see how much the
+            // height changes per unit of distance.
+            let height = |du: f64, dv: f64| {
+                let uv = (hit.uv.0 + du, hit.uv.1 + dv);
+                let point = hit.local_point + hit.local_dpdu * du + hit.local_dpdv * dv;
+                bump.color(uv, point).average()
+            };
+            let base = height(0.0, 0.0);
+            let slope = |rise: f64, run: Vec3| {
+                let length = run.length() * BUMP_STEP;
+                if length > crate::EPSILON {
+                    (rise - base) / length
+                } else {
+                    0.0
+                }
+            };
+            let slope_u = slope(height(BUMP_STEP, 0.0), hit.dpdu);
+            let slope_v = slope(height(0.0, BUMP_STEP), hit.dpdv);
+
+            // The surface leans back from the directions in which it rises.
+            let (tangent, bitangent) = tangent_frame(hit, normal);
+            normal = (normal - tangent * slope_u - bitangent * slope_v).normalize();
+        }
+
+        normal
+    }
+
     // create velvet material
     pub fn matt(color: Color) -> Material {
         Material {
@@ -30,6 +153,7 @@ impl Material {
             specular: 0.2,
             specular_exponent: 5.0,
             reflectiveness: 0.0,
+            ..Material::neutral()
         }
     }
     // create mirror material
@@ -40,6 +164,7 @@ impl Material {
             specular: 10.0,
             specular_exponent: 100.0,
             reflectiveness: 1.0,
+            ..Material::neutral()
         }
     }
     // create plastic material
@@ -50,6 +175,7 @@ impl Material {
             specular: 30.0,
             specular_exponent: 50.0,
             reflectiveness: 0.4,
+            ..Material::neutral()
         }
     }
 
@@ -60,6 +186,184 @@ impl Material {
             specular: 80.0,
             specular_exponent: 80.0,
             reflectiveness: 0.3,
+            ..Material::neutral()
+        }
+    }
+
+    // create glass material. Its reflections come from the Fresnel effect
+    // alone, so it reflects more light at grazing angles.
+    pub fn glass() -> Material {
+        Material {
+            color: Color::white(),
+            specular: 10.0,
+            specular_exponent: 200.0,
+            transparency: 1.0,
+            index_of_refraction: 1.5,
+            ..Material::neutral()
+        }
+    }
+
+    // create water material
+    pub fn water() -> Material {
+        Material {
+            color: Color::new(0.85, 0.95, 1.0),
+            specular: 5.0,
+            specular_exponent: 100.0,
+            transparency: 1.0,
+            index_of_refraction: 1.33,
+            ..Material::neutral()
+        }
+    }
+
+    // create diamond material
+    pub fn diamond() -> Material {
+        Material {
+            color: Color::white(),
+            specular: 20.0,
+            specular_exponent: 300.0,
+            transparency: 1.0,
+            index_of_refraction: 2.42,
+            ..Material::neutral()
+        }
+    }
+
+    // create a material glowing with the given color, such as a neon tube or
+    // a light bulb. It reflects no light of its own.
+    pub fn emissive(color: Color) -> Material {
+        Material {
+            emission: color,
+            ..Material::neutral()
+        }
+    }
+}
+
+// Return unit directions along the surface around 'normal', the first
+// following u and the second on the side of v. Hits without derivatives get
+// an arbitrary pair.
+fn tangent_frame(hit: &Hit, normal: Vec3) -> (Vec3, Vec3) {
+    let tangent = hit.dpdu - normal * hit.dpdu.dot(normal);
+    let tangent = if tangent.length() > crate::EPSILON {
+        tangent.normalize()
+    } else {
+        normal.basis().0
+    };
+
+    // Mirrored texture coordinates put v on the other side of u.
+    let bitangent = normal.cross(tangent);
+    if hit.dpdv.dot(bitangent) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::{Intersectable, Ray};
+    use crate::objects::{Cube, Cylinder, Plane, Sphere, Triangle};
+
+    // A texture of one color everywhere.
+    #[derive(Debug)]
+    struct Flat(Color);
+
+    impl Texture for Flat {
+        fn color(&self, _uv: (f64, f64), _point: Vec3) -> Color {
+            self.0
+        }
+    }
+
+    // A texture growing brighter along u.
+    #[derive(Debug)]
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn color(&self, uv: (f64, f64), _point: Vec3) -> Color {
+            Color::new(uv.0, uv.0, uv.0)
+        }
+    }
+
+    // Return hits on every kind of shape, from rays aimed at their middle.
+    fn hits() -> Vec<Hit> {
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let material = Material::neutral;
+        let objects: Vec<Box<dyn Intersectable>> = vec![
+            Box::new(Sphere {
+                position: origin,
+                radius: 1.0,
+                material: material(),
+            }),
+            Box::new(Cube {
+                position: origin,
+                dimensions: 2.0,
+                material: material(),
+            }),
+            Box::new(Cylinder {
+                center: origin,
+                radius: 1.0,
+                height: 2.0,
+                material: material(),
+            }),
+            Box::new(Plane {
+                position: origin,
+                normal: Vec3::new(0.0, 0.0, -1.0),
+                material: material(),
+            }),
+            Box::new(Triangle {
+                a: Vec3::new(-1.0, -1.0, 0.0),
+                b: Vec3::new(1.0, -1.0, 0.0),
+                c: Vec3::new(0.0, 1.0, 0.0),
+                material: material(),
+            }),
+        ];
+        let ray = Ray {
+            origin: Vec3::new(0.1, 0.2, 5.0),
+            direction: Vec3::new(-0.02, -0.04, -1.0).normalize(),
+        };
+        objects
+            .iter()
+            .map(|object| object.intersect(ray, 0.0, f64::INFINITY).unwrap())
+            .collect()
+    }
+
+    fn near(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn flat_maps_leave_the_normal_unchanged() {
+        let mut material = Material::neutral();
+        material.textures.bump = Some(Arc::new(Flat(Color::new(0.3, 0.3, 0.3))));
+        material.textures.normal = Some(Arc::new(Flat(Color::new(0.5, 0.5, 1.0))));
+        for hit in hits() {
+            let normal = material.shading_normal(&hit);
+            assert!(
+                near(normal, hit.geometric_normal),
+                "{:?} for {:?}",
+                normal,
+                hit
+            );
+        }
+    }
+
+    #[test]
+    fn maps_turn_the_normal_along_the_tangent_frame() {
+        for hit in hits() {
+            let tangent = hit.dpdu.normalize();
+
+            // A normal map pointing half way along u.
+            let mut material = Material::neutral();
+            material.textures.normal = Some(Arc::new(Flat(Color::new(1.0, 0.5, 1.0))));
+            let normal = material.shading_normal(&hit);
+            let expected = (hit.geometric_normal + tangent).normalize();
+            assert!(near(normal, expected), "{:?} is not {:?}", normal, expected);
+
+            // A surface rising along u leans back towards -u.
+            let mut material = Material::neutral();
+            material.textures.bump = Some(Arc::new(Ramp));
+            let normal = material.shading_normal(&hit);
+            assert!((normal.length() - 1.0).abs() < 1e-9);
+            assert!(normal.dot(tangent) < -1e-3 && normal.dot(hit.geometric_normal) > 0.0);
         }
     }
 }
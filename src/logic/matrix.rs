@@ -0,0 +1,285 @@
+use crate::logic::Vec3;
+use std::ops::Mul;
+
+// Define a 4x4 matrix acting on points and vectors in homogeneous
+// coordinates, stored row by row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { rows }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Create the matrix moving points by 'offset'.
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Create the matrix scaling each axis by the matching factor.
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        Mat4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Create the matrix rotating by 'degrees' around 'axis', counter-clockwise
+    // when the axis points towards the viewer.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let axis = axis.normalize();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        let (x, y, z) = (axis.x, axis.y, axis.z);
+        let (xy, xz, yz) = (t * x * y, t * x * z, t * y * z);
+
+        Mat4::new([
+            [t * x * x + cos, xy - sin * z, xz + sin * y, 0.0],
+            [xy + sin * z, t * y * y + cos, yz - sin * x, 0.0],
+            [xz - sin * y, yz + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Mat4::new(rows)
+    }
+
+    // Apply the matrix to a point, which is moved by translations.
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let r = &self.rows;
+        Vec3::new(
+            r[0][0] * point.x + r[0][1] * point.y + r[0][2] * point.z + r[0][3],
+            r[1][0] * point.x + r[1][1] * point.y + r[1][2] * point.z + r[1][3],
+            r[2][0] * point.x + r[2][1] * point.y + r[2][2] * point.z + r[2][3],
+        )
+    }
+
+    // Apply the matrix to a direction, which translations leave unchanged.
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        let r = &self.rows;
+        Vec3::new(
+            r[0][0] * vector.x + r[0][1] * vector.y + r[0][2] * vector.z,
+            r[1][0] * vector.x + r[1][1] * vector.y + r[1][2] * vector.z,
+            r[2][0] * vector.x + r[2][1] * vector.y + r[2][2] * vector.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    // Compose two matrices, applying 'rhs' first.
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Mat4::new(rows)
+    }
+}
+
+// Define a transform along with its inverse, which is needed to bring rays
+// into the space of a transformed object. Transforms are built step by step,
+// each step applying after the previous ones:
+//
+//     Transform::identity().scale(Vec3::splat(2.0)).rotate_y(45.0).translate(offset)
+//
+// scales an object, then turns it, then moves it into place.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    // Apply 'other' after this transform.
+    pub fn then(self, other: Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    pub fn translate(self, offset: Vec3) -> Transform {
+        self.then(Transform {
+            matrix: Mat4::translation(offset),
+            inverse: Mat4::translation(-offset),
+        })
+    }
+
+    // Scale each axis by the matching factor, none of which may be zero.
+    // Negative factors mirror the object.
+    pub fn scale(self, factors: Vec3) -> Transform {
+        assert!(
+            factors.x != 0.0 && factors.y != 0.0 && factors.z != 0.0,
+            "scale factors must not be zero"
+        );
+        self.then(Transform {
+            matrix: Mat4::scaling(factors),
+            inverse: Mat4::scaling(Vec3::new(
+                factors.x.recip(),
+                factors.y.recip(),
+                factors.z.recip(),
+            )),
+        })
+    }
+
+    // Rotate by 'degrees' around 'axis', passing through the origin.
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Transform {
+        self.then(Transform {
+            matrix: Mat4::rotation(axis, degrees),
+            inverse: Mat4::rotation(axis, -degrees),
+        })
+    }
+
+    pub fn rotate_x(self, degrees: f64) -> Transform {
+        self.rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(self, degrees: f64) -> Transform {
+        self.rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(self, degrees: f64) -> Transform {
+        self.rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.matrix == Mat4::identity()
+    }
+
+    pub fn point(&self, point: Vec3) -> Vec3 {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn vector(&self, vector: Vec3) -> Vec3 {
+        self.matrix.transform_vector(vector)
+    }
+
+    // Transform a surface normal, which must stay perpendicular to the
+    // surface and so uses the transpose of the inverse. The result is not
+    // normalized.
+    pub fn normal(&self, normal: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} is not {:?}", a, b);
+    }
+
+    fn assert_identity(matrix: Mat4) {
+        for (i, row) in matrix.rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!(
+                    (value - expected).abs() < 1e-9,
+                    "{:?} is not the identity",
+                    matrix
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn steps_apply_in_the_order_they_are_written() {
+        let point = Vec3::new(1.0, 0.0, 0.0);
+        let scale_then_move = Transform::identity()
+            .scale(Vec3::new(2.0, 2.0, 2.0))
+            .translate(Vec3::new(0.0, 1.0, 0.0));
+        let move_then_scale = Transform::identity()
+            .translate(Vec3::new(0.0, 1.0, 0.0))
+            .scale(Vec3::new(2.0, 2.0, 2.0));
+        assert_near(scale_then_move.point(point), Vec3::new(2.0, 1.0, 0.0));
+        assert_near(move_then_scale.point(point), Vec3::new(2.0, 2.0, 0.0));
+
+        // Rotations turn about the origin, before the object is moved.
+        let turn_then_move = Transform::identity()
+            .rotate_z(90.0)
+            .translate(Vec3::new(3.0, 0.0, 0.0));
+        assert_near(turn_then_move.point(point), Vec3::new(3.0, 1.0, 0.0));
+        assert_near(turn_then_move.vector(point), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn transform_inverse_brings_points_back() {
+        let transform = Transform::identity()
+            .scale(Vec3::new(1.0, 3.0, 0.5))
+            .rotate(Vec3::new(1.0, 1.0, 0.0), 60.0)
+            .translate(Vec3::new(-4.0, 2.0, 7.0));
+        let point = Vec3::new(0.3, -1.2, 2.5);
+        assert_near(transform.inverse().point(transform.point(point)), point);
+        assert_identity(transform.matrix * transform.inverse);
+        assert_identity(transform.inverse * transform.matrix);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_stretched_surfaces() {
+        // A unit sphere squashed into an ellipsoid, turned and moved.
+        let (a, b, c) = (2.0, 0.5, 1.0);
+        let transform = Transform::identity()
+            .scale(Vec3::new(a, b, c))
+            .rotate_y(30.0)
+            .translate(Vec3::new(1.0, 2.0, 3.0));
+        let turn = Transform::identity().rotate_y(30.0);
+
+        let on_sphere = Vec3::new(1.0, 1.0, 1.0).normalize();
+        let normal = transform.normal(on_sphere).normalize();
+
+        // The gradient of x²/a² + y²/b² + z²/c² at the point of the
+        // ellipsoid, before it is turned, is the true normal.
+        let p = Vec3::new(a * on_sphere.x, b * on_sphere.y, c * on_sphere.z);
+        let gradient = Vec3::new(p.x / (a * a), p.y / (b * b), p.z / (c * c));
+        assert_near(normal, turn.vector(gradient).normalize());
+
+        // Transforming the normal as a direction would tilt it.
+        let tangent = transform.vector(Vec3::new(-on_sphere.y, on_sphere.x, 0.0));
+        assert!(normal.dot(tangent).abs() < 1e-9);
+        assert!(transform.vector(on_sphere).normalize().dot(tangent).abs() > 1e-3);
+    }
+}
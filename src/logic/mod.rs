@@ -1,6 +1,3 @@
-// Disable dead code warnings for the entire module.
-#![allow(dead_code)]
-
 mod camera;
 mod light;
 mod ray;
@@ -11,16 +8,70 @@ mod materials;
 mod options;
 mod intersectable;
 mod helpers;
+mod parser;
+mod aabb;
+mod bvh;
+mod world;
+mod random;
+mod obj;
+mod sampling;
+mod projection;
+mod matrix;
+mod texture;
+mod integrator;
+mod environment;
+mod output;
+mod tonemap;
+mod error;
 
 pub use camera::Camera;
+pub use camera::Fov;
+pub use projection::Equirectangular;
+pub use projection::Fisheye;
+pub use projection::FisheyeMapping;
+pub use projection::Orthographic;
+pub use projection::Projection;
+pub use light::AreaShape;
+pub use light::Attenuation;
 pub use light::Light;
 pub use light::LightType;
 pub use light::create_spotlight;
 pub use ray::Ray;
+pub use matrix::Transform;
 pub use scene::Scene;
 pub use vector::Vec3;
 pub use colors::Color;
 pub use materials::Material;
+pub use texture::Checker;
+pub use texture::Gradient;
+pub use texture::ImageTexture;
+pub use texture::Marble;
+pub use texture::Noise;
+pub use texture::Stripes;
+pub use texture::Texture;
+pub use texture::UvAxis;
+pub use texture::Wood;
 pub use options::Options;
+pub use integrator::Integrator;
+pub use environment::Environment;
+pub use environment::EnvironmentMap;
+pub use environment::Sky;
+pub use output::ExrCompression;
+pub use output::ExrPrecision;
+pub use output::OutputFormat;
+pub use tonemap::ToneMap;
+pub use tonemap::ToneMapper;
+pub use tonemap::Transfer;
+pub use intersectable::Hit;
 pub use intersectable::Intersectable;
 pub use helpers::progress_bar;
+pub use parser::SceneFile;
+pub use error::Error;
+pub use aabb::Aabb;
+pub use bvh::Bvh;
+pub use world::World;
+pub use random::Rng;
+pub use sampling::Filter;
+pub use sampling::FilterTable;
+pub use sampling::Sampler;
+pub use sampling::pixel_rng;
@@ -0,0 +1,641 @@
+// Loader for Wavefront OBJ models and the MTL material libraries they use.
+//
+// Faces are grouped into one mesh per group ("g" or "o") and material
+// ("usemtl"). Polygons with more than three corners are split into
+// triangles. Statements the renderer has no use for are skipped.
+use crate::logic::Color;
+use crate::logic::ImageTexture;
+use crate::logic::Material;
+use crate::logic::Vec3;
+use crate::objects::Mesh;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// An error while loading a model or one of its material libraries.
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}: line {}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// A mesh of a model, holding the faces of one group drawn with one material.
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub group: String,
+    pub mesh: Mesh,
+}
+
+// A corner of a face: indices of its position, texture coordinate and normal.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// The faces of one group and material, with the corners they use. Corners
+// are shared between faces, each distinct corner becoming a mesh vertex.
+struct MeshBuilder {
+    group: String,
+    material: Option<String>,
+    corners: Vec<Corner>,
+    lookup: HashMap<Corner, usize>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, corner: Corner) -> usize {
+        let corners = &mut self.corners;
+        *self.lookup.entry(corner).or_insert_with(|| {
+            corners.push(corner);
+            corners.len() - 1
+        })
+    }
+}
+
+// Load the model at 'path'. Faces drawn before any "usemtl" use the default
+// material. Meshes without normals in the file are flat shaded, or smooth
+// shaded when 'smooth' is set.
+pub fn load_obj(
+    path: &Path,
+    default_material: Material,
+    smooth: bool,
+) -> Result<Vec<ObjMesh>, ObjError> {
+    parse_obj(&read(path)?, path, default_material, smooth)
+}
+
+// Parse the text of the model at 'path', which names it in errors and
+// locates the material libraries it uses.
+fn parse_obj(
+    source: &str,
+    path: &Path,
+    default_material: Material,
+    smooth: bool,
+) -> Result<Vec<ObjMesh>, ObjError> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let error = |line: usize, message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut builders: Vec<MeshBuilder> = Vec::new();
+    let mut group = "default".to_string();
+    let mut material: Option<String> = None;
+    let mut current: Option<usize> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let values: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => positions.push(vector(&values, 3).map_err(|message| error(number, message))?),
+            "vn" => normals.push(vector(&values, 3).map_err(|message| error(number, message))?),
+            "vt" => {
+                let uv = numbers(&values, 1).map_err(|message| error(number, message))?;
+                uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "g" | "o" => {
+                group = if values.is_empty() {
+                    "default".to_string()
+                } else {
+                    values.join(" ")
+                };
+                current = None;
+            }
+            "usemtl" => {
+                let name = values.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(error(number, format!("unknown material '{}'", name)));
+                }
+                material = Some(name);
+                current = None;
+            }
+            "mtllib" => {
+                for library in values {
+                    materials.extend(load_mtl(&directory.join(library))?);
+                }
+            }
+            "f" => {
+                if values.len() < 3 {
+                    return Err(error(number, "a face needs at least 3 corners".to_string()));
+                }
+                let corners = values
+                    .iter()
+                    .map(|text| corner(text, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<Corner>, String>>()
+                    .map_err(|message| error(number, message))?;
+
+                // Find or start the mesh of the current group and material.
+                let index = match current {
+                    Some(index) => index,
+                    None => {
+                        let index = builders
+                            .iter()
+                            .position(|b| b.group == group && b.material == material)
+                            .unwrap_or_else(|| {
+                                builders.push(MeshBuilder {
+                                    group: group.clone(),
+                                    material: material.clone(),
+                                    corners: Vec::new(),
+                                    lookup: HashMap::new(),
+                                    triangles: Vec::new(),
+                                });
+                                builders.len() - 1
+                            });
+                        current = Some(index);
+                        index
+                    }
+                };
+
+                let builder = &mut builders[index];
+                let vertices: Vec<usize> = corners.iter().map(|&c| builder.vertex(c)).collect();
+                let points: Vec<Vec3> = corners.iter().map(|c| positions[c.0]).collect();
+                for [a, b, c] in triangulate(&points) {
+                    builder
+                        .triangles
+                        .push([vertices[a], vertices[b], vertices[c]]);
+                }
+            }
+            _ => {} // Lines, points, smoothing groups and others are not rendered
+        }
+    }
+
+    if builders.is_empty() {
+        return Err(error(
+            source.lines().count(),
+            "the model has no faces".to_string(),
+        ));
+    }
+
+    let meshes = builders
+        .into_iter()
+        .map(|builder| {
+            let material_of = builder
+                .material
+                .as_ref()
+                .and_then(|name| materials.get(name))
+                .cloned()
+                .unwrap_or_else(|| default_material.clone());
+            let vertex_positions = builder.corners.iter().map(|c| positions[c.0]).collect();
+            let mut mesh = Mesh::new(vertex_positions, builder.triangles, material_of);
+
+            if builder.corners.iter().all(|c| c.1.is_some()) {
+                mesh = mesh.with_uvs(builder.corners.iter().map(|c| uvs[c.1.unwrap()]).collect());
+            }
+            if builder.corners.iter().all(|c| c.2.is_some()) {
+                mesh = mesh.with_normals(
+                    builder
+                        .corners
+                        .iter()
+                        .map(|c| normals[c.2.unwrap()])
+                        .collect(),
+                );
+            } else if smooth {
+                mesh = mesh.smooth();
+            }
+
+            ObjMesh {
+                group: builder.group,
+                mesh,
+            }
+        })
+        .collect();
+    Ok(meshes)
+}
+
+// Load the materials of an MTL library. The diffuse color (Kd) becomes the
+// material color, the average of the specular color (Ks) its specular
+// strength, and the specular exponent (Ns) and the emitted color (Ke) are
+// used as is. A diffuse texture (map_Kd) replaces the color, ignoring the
+// options given before its file.
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    parse_mtl(&read(path)?, path)
+}
+
+// Parse the text of the material library at 'path', which names it in
+// errors and locates its textures.
+fn parse_mtl(source: &str, path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let error = |line: usize, message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let values: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            let material = Material {
+                color: Color::white(),
+                diffuse: 1.0,
+                ..Material::neutral()
+            };
+            current = Some((values.join(" "), material));
+            continue;
+        }
+
+        let material = match (&mut current, keyword) {
+            (Some((_, material)), _) => material,
+            (None, "Kd" | "Ks" | "Ns" | "Ke") => {
+                return Err(error(number, format!("'{}' before any 'newmtl'", keyword)))
+            }
+            (None, _) => continue,
+        };
+
+        match keyword {
+            "Kd" => {
+                let kd = vector(&values, 3).map_err(|message| error(number, message))?;
+                material.color = Color::new(kd.x, kd.y, kd.z);
+            }
+            "Ks" => {
+                let ks = vector(&values, 3).map_err(|message| error(number, message))?;
+                material.specular = (ks.x + ks.y + ks.z) / 3.0;
+            }
+            "Ke" => {
+                let ke = vector(&values, 3).map_err(|message| error(number, message))?;
+                material.emission = Color::new(ke.x, ke.y, ke.z);
+            }
+            "Ns" => {
+                material.specular_exponent =
+                    numbers(&values, 1).map_err(|message| error(number, message))?[0]
+            }
+            "map_Kd" => {
+                // Options such as "-s" come before the file name.
+                let file = values
+                    .last()
+                    .ok_or_else(|| error(number, "'map_Kd' needs a file".to_string()))?;
+                let image_path = path.parent().unwrap_or(Path::new("")).join(file);
+                let image = ImageTexture::open(&image_path)
+                    .map_err(|err| error(number, format!("cannot load '{}': {}", file, err)))?;
+                material.textures.color = Some(Arc::new(image));
+            }
+            _ => {} // Other properties have no counterpart in Material
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|error| ObjError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+// Parse at least 'count' numbers.
+fn numbers(values: &[&str], count: usize) -> Result<Vec<f64>, String> {
+    if values.len() < count {
+        return Err(format!(
+            "expected {} numbers, found {}",
+            count,
+            values.len()
+        ));
+    }
+    values
+        .iter()
+        .map(|text| match text.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("expected a number, found '{}'", text)),
+        })
+        .collect()
+}
+
+fn vector(values: &[&str], count: usize) -> Result<Vec3, String> {
+    let values = numbers(values, count)?;
+    Ok(Vec3::new(values[0], values[1], values[2]))
+}
+
+// Parse a face corner written as "v", "v/vt", "v//vn" or "v/vt/vn". Indices
+// count from 1, or backwards from the last element when negative.
+fn corner(text: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, String> {
+    let mut parts = text.split('/');
+    let position = index(parts.next().unwrap_or(""), positions, "vertex")?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(index(part, uvs, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(index(part, normals, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face corner '{}'", text));
+    }
+    Ok((position, uv, normal))
+}
+
+fn index(text: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = text
+        .parse()
+        .map_err(|_| format!("expected a {} index, found '{}'", kind, text))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} is out of range, {} defined so far",
+            kind, value, count
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// Split a polygon into triangles by repeatedly cutting off a corner (an
+// "ear") that no other corner lies in, which also works for concave
+// polygons. Returns triangles as indices into 'points'.
+fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    if count == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // The polygon's normal, using Newell's method so it is robust to
+    // slightly non planar polygons.
+    let mut normal = Vec3::zero();
+    for i in 0..count {
+        let (a, b) = (points[i], points[(i + 1) % count]);
+        normal = normal
+            + Vec3::new(
+                (a.y - b.y) * (a.z + b.z),
+                (a.z - b.z) * (a.x + b.x),
+                (a.x - b.x) * (a.y + b.y),
+            );
+    }
+
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count - 2);
+
+    while remaining.len() > 3 {
+        let size = remaining.len();
+        let ear = (0..size).find(|&i| {
+            let (prev, current, next) = (
+                remaining[(i + size - 1) % size],
+                remaining[i],
+                remaining[(i + 1) % size],
+            );
+            let (a, b, c) = (points[prev], points[current], points[next]);
+
+            // The corner must be convex and hold no other corner.
+            (b - a).cross(c - b).dot(normal) > 0.0
+                && !remaining.iter().any(|&other| {
+                    other != prev
+                        && other != current
+                        && other != next
+                        && inside_triangle(points[other], a, b, c, normal)
+                })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + size - 1) % size],
+                    remaining[i],
+                    remaining[(i + 1) % size],
+                ]);
+                remaining.remove(i);
+            }
+            None => break, // Degenerate polygon, fan out what is left
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+fn inside_triangle(point: Vec3, a: Vec3, b: Vec3, c: Vec3, normal: Vec3) -> bool {
+    (b - a).cross(point - a).dot(normal) >= 0.0
+        && (c - b).cross(point - b).dot(normal) >= 0.0
+        && (a - c).cross(point - c).dot(normal) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::Intersectable;
+    use crate::logic::Ray;
+
+    fn parse(source: &str) -> Result<Vec<ObjMesh>, ObjError> {
+        parse_obj(
+            source,
+            Path::new("model.obj"),
+            Material::matt(Color::white()),
+            false,
+        )
+    }
+
+    // Return the message of the error 'source' gives, with its line.
+    fn error(source: &str) -> String {
+        parse(source)
+            .expect_err("the model should be rejected")
+            .to_string()
+    }
+
+    // Shoot a ray down the z axis at (x, y) and return the hit, if any.
+    fn hit_at(mesh: &Mesh, x: f64, y: f64) -> Option<crate::logic::Hit> {
+        let ray = Ray {
+            origin: Vec3::new(x, y, 5.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        mesh.intersect(ray, 1e-6, f64::INFINITY)
+    }
+
+    fn area(a: Vec3, b: Vec3, c: Vec3, normal: Vec3) -> f64 {
+        (b - a).cross(c - a).dot(normal) / 2.0
+    }
+
+    #[test]
+    fn concave_polygons_are_split_into_ears() {
+        // An L shape starting at a corner from which a fan would leave it.
+        let points = [
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+        ]
+        .map(|(x, y)| Vec3::new(x, y, 0.0));
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let mut total = 0.0;
+        for [a, b, c] in triangles {
+            let area = area(points[a], points[b], points[c], normal);
+            assert!(area > 0.0, "triangle {:?} is turned over", [a, b, c]);
+            total += area;
+        }
+        assert!((total - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn concave_faces_cover_only_the_polygon() {
+        let meshes = parse(
+            "v 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nv 0 0 0\nv 2 0 0\n\
+             f 1 2 3 4 5 6\n",
+        )
+        .unwrap();
+        let mesh = &meshes[0].mesh;
+        assert!(hit_at(mesh, 1.5, 0.5).is_some());
+        assert!(hit_at(mesh, 0.5, 1.5).is_some());
+        assert!(hit_at(mesh, 1.5, 1.5).is_none());
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        let relative = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\n").unwrap();
+        let absolute = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nv 5 5 5\n").unwrap();
+        for meshes in [relative, absolute] {
+            let hit = hit_at(&meshes[0].mesh, 0.25, 0.25).expect("the face should be hit");
+            assert!((hit.distance - 5.0).abs() < 1e-9);
+        }
+        assert_eq!(corner("-1//-2", 4, 0, 2), Ok((3, None, Some(0))));
+    }
+
+    #[test]
+    fn face_corners() {
+        assert_eq!(corner("3", 3, 0, 0), Ok((2, None, None)));
+        assert_eq!(corner("3/2", 3, 2, 0), Ok((2, Some(1), None)));
+        assert_eq!(corner("3//1", 3, 0, 1), Ok((2, None, Some(0))));
+        assert_eq!(corner("3/2/1", 3, 2, 1), Ok((2, Some(1), Some(0))));
+        assert_eq!(
+            corner("1/1/1/1", 1, 1, 1),
+            Err("invalid face corner '1/1/1/1'".to_string())
+        );
+    }
+
+    #[test]
+    fn texture_coordinates_and_normals() {
+        let meshes = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 0 1\n\
+             vn 0 0.6 0.8\n\
+             f 1/1/1 2/2/1 3/3/1\n",
+        )
+        .unwrap();
+        let hit = hit_at(&meshes[0].mesh, 0.25, 0.5).expect("the face should be hit");
+        assert!((hit.uv.0 - 0.25).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9);
+        assert!((hit.normal - Vec3::new(0.0, 0.6, 0.8)).length() < 1e-9);
+        assert!((hit.geometric_normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn groups_split_the_model() {
+        let meshes = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             g first\nf 1 2 3\n\
+             o second\nf 1 2 3\n\
+             g first\nf 3 2 1\n",
+        )
+        .unwrap();
+        let groups: Vec<&str> = meshes.iter().map(|mesh| mesh.group.as_str()).collect();
+        assert_eq!(groups, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        assert_eq!(
+            error("v 0 0 0\nv 1 0\n"),
+            "model.obj: line 2: expected 3 numbers, found 2"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 y\n"),
+            "model.obj: line 3: expected a number, found 'y'"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            "model.obj: line 3: a face needs at least 3 corners"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"),
+            "model.obj: line 4: vertex index 4 is out of range, 3 defined so far"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"),
+            "model.obj: line 4: vertex index 0 is out of range, 3 defined so far"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/a 2 3\n"),
+            "model.obj: line 4: expected a texture coordinate index, found 'a'"
+        );
+        assert_eq!(
+            error("usemtl missing\n"),
+            "model.obj: line 1: unknown material 'missing'"
+        );
+        assert_eq!(
+            error("v 0 0 0\n# nothing else\n"),
+            "model.obj: line 2: the model has no faces"
+        );
+    }
+
+    #[test]
+    fn material_libraries() {
+        let materials = parse_mtl(
+            "newmtl red\nKd 1 0 0\nKs 0.5 0.5 0.5\nNs 20\n\
+             newmtl lamp\nKe 1 1 0.5\n",
+            Path::new("model.mtl"),
+        )
+        .unwrap();
+        let red = &materials["red"];
+        assert_eq!(red.color, Color::new(1.0, 0.0, 0.0));
+        assert_eq!((red.specular, red.specular_exponent), (0.5, 20.0));
+        assert_eq!(materials["lamp"].emission, Color::new(1.0, 1.0, 0.5));
+
+        let err = parse_mtl("Kd 1 0 0\n", Path::new("model.mtl")).expect_err("no material yet");
+        assert_eq!(
+            err.to_string(),
+            "model.mtl: line 1: 'Kd' before any 'newmtl'"
+        );
+    }
+}
@@ -1,10 +1,71 @@
+use crate::logic::ExrCompression;
+use crate::logic::ExrPrecision;
+use crate::logic::Filter;
+use crate::logic::Integrator;
+use crate::logic::OutputFormat;
+use crate::logic::Sampler;
+use crate::logic::ToneMap;
+use crate::logic::Transfer;
+
 // Define a struct to represent rendering options.
 #[derive(Debug)]
 pub struct Options {
-    pub max_rays: u8,      // Maximum number of rays for rendering
-    pub gamma: f64,        // Gamma correction factor for final color
-    pub diffuse: bool,     // Enable/disable diffuse reflection
-    pub specular: bool,    // Enable/disable specular reflection
-    pub shadows: bool,     // Enable/disable shadows
-    pub reflections: bool, // Enable/disable reflections
+    pub max_rays: u8,                    // Maximum number of rays for rendering
+    pub gamma: f64,                      // Gamma correction factor for final color
+    pub exposure: f64,                   // Brightening of the image in stops, 1 doubles the light
+    pub tone_map: ToneMap,               // Mapping of light brighter than white into the image
+    pub white_point: Option<f64>,        // Light turned into white, None for the tone map's own
+    pub transfer: Transfer,              // Encoding of the tone mapped light
+    pub diffuse: bool,                   // Enable/disable diffuse reflection
+    pub specular: bool,                  // Enable/disable specular reflection
+    pub shadows: bool,                   // Enable/disable shadows
+    pub reflections: bool,               // Enable/disable reflections
+    pub refractions: bool, // Enable/disable light passing through transparent materials
+    pub threads: usize,    // Number of rendering threads, 0 uses every core
+    pub samples: u32,      // Number of rays traced per pixel
+    pub sampler: Sampler,  // Placement of the samples within a pixel
+    pub filter: Filter,    // Weighting of the samples into the pixel color
+    pub seed: u64,         // Seed of the random sample placement
+    pub integrator: Integrator, // Way of computing the light seen along each ray
+    pub format: Option<OutputFormat>, // Format of the image file, None to go by its extension
+    pub exr_precision: ExrPrecision, // Size of the numbers in OpenEXR files
+    pub exr_compression: ExrCompression, // Compression of OpenEXR files
+}
+
+impl Options {
+    // Return the number of rendering threads to use.
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            std::thread::available_parallelism().map_or(1, |count| count.get())
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            max_rays: 4,
+            gamma: 1.0,
+            exposure: 0.0,
+            tone_map: ToneMap::Clip,
+            white_point: None,
+            transfer: Transfer::Gamma,
+            diffuse: true,
+            specular: true,
+            shadows: true,
+            reflections: true,
+            refractions: true,
+            threads: 0,
+            samples: 1,
+            sampler: Sampler::Grid,
+            filter: Filter::Box,
+            seed: 0,
+            integrator: Integrator::Whitted,
+            format: None,
+            exr_precision: ExrPrecision::Half,
+            exr_compression: ExrCompression::Zip,
+        }
+    }
 }
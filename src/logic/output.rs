@@ -0,0 +1,381 @@
+use crate::logic::Color;
+use crate::logic::Options;
+use exr::prelude::{f16, Blocks, Compression, LineOrder, Vec2};
+use exr::prelude::{Encoding, Image, Layer, LayerAttributes, SpecificChannels, WritableImage};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::{ColorType, ImageBuffer, ImageError, Rgb};
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// Define the formats rendered images are saved in. The high dynamic range
+// ones, OpenEXR, Radiance and PFM, keep the full range of light, brighter
+// than white included: their pixels hold the light as it was computed,
+// without tone mapping, as compositing software expects. The others hold the
+// tone mapped colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Png,      // 8-bit PNG
+    Png16,    // 16-bit PNG, smoother gradients for later editing
+    Ppm,      // Binary PPM (P6)
+    PpmAscii, // Plain text PPM (P3), one pixel per line
+    Tga,      // Truevision TGA
+    Bmp,      // Windows bitmap
+    Exr,      // OpenEXR, with half or full floats, see ExrPrecision and ExrCompression
+    Radiance, // Radiance .hdr, with a shared exponent for the three colors (RGBE)
+    Pfm,      // Portable float map, uncompressed 32-bit floats
+}
+
+// Define the size of the floating point numbers in OpenEXR files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrPrecision {
+    Half,  // 16-bit floats, precise enough for pictures and half the size
+    Float, // 32-bit floats
+}
+
+// Define how the scanlines of OpenEXR files are compressed, both losslessly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrCompression {
+    None, // Uncompressed
+    Zip,  // ZIP over blocks of 16 scanlines
+}
+
+impl OutputFormat {
+    // Return the format matching the extension of 'path'. PNG files are 8-bit
+    // and PPM files binary, the other variants need the format option.
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "ppm" | "pnm" => Some(OutputFormat::Ppm),
+            "tga" => Some(OutputFormat::Tga),
+            "bmp" => Some(OutputFormat::Bmp),
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Radiance),
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    // Tell whether the format keeps light brighter than white, in which case
+    // it is given the light before tone mapping.
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Exr | OutputFormat::Radiance | OutputFormat::Pfm
+        )
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<OutputFormat, String> {
+        match text {
+            "png" => Ok(OutputFormat::Png),
+            "png16" => Ok(OutputFormat::Png16),
+            "ppm" => Ok(OutputFormat::Ppm),
+            "ppm_ascii" => Ok(OutputFormat::PpmAscii),
+            "tga" => Ok(OutputFormat::Tga),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "exr" => Ok(OutputFormat::Exr),
+            "hdr" => Ok(OutputFormat::Radiance),
+            "pfm" => Ok(OutputFormat::Pfm),
+            _ => Err("expected png, png16, ppm, ppm_ascii, tga, bmp, exr, hdr or pfm".to_string()),
+        }
+    }
+}
+
+impl FromStr for ExrPrecision {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ExrPrecision, String> {
+        match text {
+            "half" => Ok(ExrPrecision::Half),
+            "float" => Ok(ExrPrecision::Float),
+            _ => Err("expected half or float".to_string()),
+        }
+    }
+}
+
+impl FromStr for ExrCompression {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ExrCompression, String> {
+        match text {
+            "none" => Ok(ExrCompression::None),
+            "zip" => Ok(ExrCompression::Zip),
+            _ => Err("expected none or zip".to_string()),
+        }
+    }
+}
+
+// Write the pixels, in row order from the top, to 'path' in 'format'. The
+// pixels hold light for the high dynamic range formats and tone mapped
+// colors between 0 and 1 for the others.
+pub fn write_image(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+    format: OutputFormat,
+    options: &Options,
+) -> io::Result<()> {
+    let rgb8 = || -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(width, height, |x, y| {
+            pixels[y as usize * width as usize + x as usize].rgb8()
+        })
+    };
+    let result = match format {
+        OutputFormat::Png => rgb8().save_with_format(path, image::ImageFormat::Png),
+        OutputFormat::Png16 => {
+            let image: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_fn(width, height, |x, y| {
+                    pixels[y as usize * width as usize + x as usize].rgb16()
+                });
+            image.save_with_format(path, image::ImageFormat::Png)
+        }
+        OutputFormat::Ppm | OutputFormat::PpmAscii => {
+            let encoding = match format {
+                OutputFormat::Ppm => SampleEncoding::Binary,
+                _ => SampleEncoding::Ascii,
+            };
+            let mut file = BufWriter::new(File::create(path)?);
+            PnmEncoder::new(&mut file)
+                .with_subtype(PnmSubtype::Pixmap(encoding))
+                .encode(rgb8().as_raw().as_slice(), width, height, ColorType::Rgb8)
+                .and_then(|()| file.flush().map_err(ImageError::IoError))
+        }
+        OutputFormat::Tga => rgb8().save_with_format(path, image::ImageFormat::Tga),
+        OutputFormat::Bmp => rgb8().save_with_format(path, image::ImageFormat::Bmp),
+        OutputFormat::Exr => {
+            return write_exr(
+                path,
+                width,
+                height,
+                pixels,
+                options.exr_precision,
+                options.exr_compression,
+            )
+        }
+        OutputFormat::Radiance => return write_radiance(path, width, height, pixels),
+        OutputFormat::Pfm => return write_pfm(path, width, height, pixels),
+    };
+    result.map_err(io_error)
+}
+
+// Return the I/O error behind an error of the image crate, or describe it.
+fn io_error(err: ImageError) -> io::Error {
+    match err {
+        ImageError::IoError(err) => err,
+        err => io::Error::other(err.to_string()),
+    }
+}
+
+// Write the pixels, in row order from the top, to an OpenEXR file with a
+// single RGB layer stored in scanlines.
+fn write_exr(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+    precision: ExrPrecision,
+    compression: ExrCompression,
+) -> io::Result<()> {
+    let encoding = Encoding {
+        compression: match compression {
+            ExrCompression::None => Compression::Uncompressed,
+            ExrCompression::Zip => Compression::ZIP16,
+        },
+        blocks: Blocks::ScanLines,
+        line_order: LineOrder::Increasing,
+    };
+    let size = (width as usize, height as usize);
+    let pixel = |position: Vec2<usize>| {
+        let (r, g, b) = pixels[position.y() * width as usize + position.x()].components();
+        (r as f32, g as f32, b as f32)
+    };
+
+    let result = match precision {
+        ExrPrecision::Half => {
+            let channels = SpecificChannels::rgb(|position| {
+                let (r, g, b) = pixel(position);
+                (f16::from_f32(r), f16::from_f32(g), f16::from_f32(b))
+            });
+            let layer = Layer::new(size, LayerAttributes::default(), encoding, channels);
+            Image::from_layer(layer).write().to_file(path)
+        }
+        ExrPrecision::Float => {
+            let channels = SpecificChannels::rgb(pixel);
+            let layer = Layer::new(size, LayerAttributes::default(), encoding, channels);
+            Image::from_layer(layer).write().to_file(path)
+        }
+    };
+    result.map_err(|err| match err {
+        exr::error::Error::Io(err) => err,
+        err => io::Error::other(err.to_string()),
+    })
+}
+
+// Write the pixels, in row order from the top, to a Radiance .hdr file.
+fn write_radiance(path: &Path, width: u32, height: u32, pixels: &[Color]) -> io::Result<()> {
+    let data: Vec<Rgb<f32>> = pixels
+        .iter()
+        .map(|color| {
+            let (r, g, b) = color.components();
+            Rgb([r as f32, g as f32, b as f32])
+        })
+        .collect();
+    let file = BufWriter::new(File::create(path)?);
+    HdrEncoder::new(file)
+        .encode(&data, width as usize, height as usize)
+        .map_err(io_error)
+}
+
+// Write the pixels, in row order from the top, to a portable float map. The
+// format stores the rows from the bottom up, and a negative scale marks the
+// numbers as little-endian.
+fn write_pfm(path: &Path, width: u32, height: u32, pixels: &[Color]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width as usize).rev() {
+        for color in row {
+            let (r, g, b) = color.components();
+            for value in [r, g, b] {
+                file.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::read_first_rgba_layer_from_file;
+    use std::path::PathBuf;
+
+    const WIDTH: u32 = 3;
+    const HEIGHT: u32 = 2;
+
+    // Pixels in row order from the top, some brighter than white.
+    fn pixels() -> Vec<Color> {
+        vec![
+            Color::new(4.0, 0.25, 1.5),
+            Color::new(0.5, 1.0, 0.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.1, 0.2, 0.3),
+            Color::new(2.0, 2.0, 2.0),
+            Color::new(1.0, 0.0, 0.5),
+        ]
+    }
+
+    // Return a path for a file of the test, removed by 'read_back'.
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rt-output-{}-{}", std::process::id(), name))
+    }
+
+    fn read_back(path: &Path) -> Vec<u8> {
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn pfm_stores_little_endian_rows_from_the_bottom() {
+        let path = temporary("image.pfm");
+        write_pfm(&path, WIDTH, HEIGHT, &pixels()).unwrap();
+        let bytes = read_back(&path);
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        let expected: Vec<f32> = pixels()
+            .chunks(WIDTH as usize)
+            .rev()
+            .flatten()
+            .flat_map(|color| {
+                let (r, g, b) = color.components();
+                [r as f32, g as f32, b as f32]
+            })
+            .collect();
+        assert_eq!(values, expected);
+        assert_eq!(&values[..3], &[0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn exr_keeps_the_light_at_both_precisions() {
+        for (precision, compression) in [
+            (ExrPrecision::Half, ExrCompression::Zip),
+            (ExrPrecision::Float, ExrCompression::None),
+        ] {
+            let path = temporary("image.exr");
+            write_exr(&path, WIDTH, HEIGHT, &pixels(), precision, compression).unwrap();
+            let image = read_first_rgba_layer_from_file(
+                &path,
+                |resolution, _| vec![(0.0, 0.0, 0.0); resolution.width() * resolution.height()],
+                |values: &mut Vec<(f32, f32, f32)>,
+                 position,
+                 (r, g, b, _): (f32, f32, f32, f32)| {
+                    values[position.y() * WIDTH as usize + position.x()] = (r, g, b);
+                },
+            )
+            .unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let size = image.layer_data.size;
+            assert_eq!((size.width(), size.height()), (3, 2));
+            let round = |value: f64| match precision {
+                ExrPrecision::Half => f16::from_f64(value).to_f32(),
+                ExrPrecision::Float => value as f32,
+            };
+            let expected: Vec<(f32, f32, f32)> = pixels()
+                .iter()
+                .map(|color| {
+                    let (r, g, b) = color.components();
+                    (round(r), round(g), round(b))
+                })
+                .collect();
+            assert_eq!(image.layer_data.channel_data.pixels, expected);
+        }
+        // Half floats cannot hold 0.1 exactly, full floats hold it as f32.
+        assert_ne!(f16::from_f64(0.1).to_f32(), 0.1);
+    }
+
+    #[test]
+    fn radiance_stores_a_shared_exponent() {
+        let path = temporary("image.hdr");
+        write_radiance(&path, WIDTH, HEIGHT, &pixels()).unwrap();
+        let bytes = read_back(&path);
+
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with("#?RADIANCE\n"));
+        assert!(text.contains("\nFORMAT=32-bit_rle_rgbe\n\n"));
+        let dimensions = b"-Y 2 +X 3\n";
+        let start = bytes
+            .windows(dimensions.len())
+            .position(|window| window == dimensions)
+            .expect("the file gives its dimensions")
+            + dimensions.len();
+
+        // Rows narrower than 8 pixels are stored flat, four bytes a pixel:
+        // each color is value / 2^(e - 128) * 256.
+        assert_eq!(
+            &bytes[start..],
+            &[
+                128, 8, 48, 131, // 4, 0.25, 1.5 over 2^3
+                64, 128, 0, 129, // 0.5, 1, 0 over 2^1
+                0, 0, 0, 0, // black
+                51, 102, 153, 127, // 0.1, 0.2, 0.3 over 2^-1
+                128, 128, 128, 130, // 2, 2, 2 over 2^2
+                128, 0, 64, 129, // 1, 0, 0.5 over 2^1
+            ]
+        );
+    }
+}
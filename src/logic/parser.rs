@@ -0,0 +1,1697 @@
+// Parser for the text scene description format documented in the README.
+//
+// A scene file is a sequence of whitespace separated tokens. Everything after
+// a '#' up to the end of the line is a comment. Top level items are either
+// simple settings ("width 1920") or blocks ("sphere { ... }") holding
+// properties of the form "name value...".
+use crate::logic::create_spotlight;
+use crate::logic::obj;
+use crate::logic::AreaShape;
+use crate::logic::Attenuation;
+use crate::logic::Camera;
+use crate::logic::Checker;
+use crate::logic::Color;
+use crate::logic::Environment;
+use crate::logic::EnvironmentMap;
+use crate::logic::Equirectangular;
+use crate::logic::Error;
+use crate::logic::Fisheye;
+use crate::logic::FisheyeMapping;
+use crate::logic::Fov;
+use crate::logic::Gradient;
+use crate::logic::ImageTexture;
+use crate::logic::Intersectable;
+use crate::logic::Light;
+use crate::logic::LightType;
+use crate::logic::Marble;
+use crate::logic::Material;
+use crate::logic::Noise;
+use crate::logic::Options;
+use crate::logic::Orthographic;
+use crate::logic::Projection;
+use crate::logic::Scene;
+use crate::logic::Sky;
+use crate::logic::Stripes;
+use crate::logic::Texture;
+use crate::logic::Transform;
+use crate::logic::UvAxis;
+use crate::logic::Vec3;
+use crate::logic::Wood;
+use crate::objects;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// An error in a scene file, pointing at the offending token.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// The camera settings of a scene file. The camera itself is only built once
+// the final image size (and so the aspect ratio) is known.
+#[derive(Clone, Debug)]
+pub struct CameraSpec {
+    pub name: String,
+    pub position: Vec3,
+    pub look_at: Vec3,
+    pub up: Vec3,
+    pub roll: f64,
+    pub projection: ProjectionSpec,
+}
+
+// The kind of camera, along with the settings only it has.
+#[derive(Clone, Debug)]
+pub enum ProjectionSpec {
+    Perspective {
+        fov: Fov,
+        aperture: f64,
+        focus_distance: Option<f64>,
+    },
+    Orthographic {
+        size: f64,        // Size of the view, in scene units
+        horizontal: bool, // Whether the size is the width rather than the height
+    },
+    Fisheye {
+        angle: f64,
+        mapping: FisheyeMapping,
+    },
+    Equirectangular,
+}
+
+impl CameraSpec {
+    pub fn build(&self, aspect_ratio: f64) -> Box<dyn Projection> {
+        let (position, look_at, up, roll) = (self.position, self.look_at, self.up, self.roll);
+
+        match self.projection {
+            ProjectionSpec::Perspective {
+                fov,
+                aperture,
+                focus_distance,
+            } => Box::new(
+                Camera::looking_at(position, look_at, up, fov, aspect_ratio, roll)
+                    .with_lens(aperture, focus_distance),
+            ),
+            ProjectionSpec::Orthographic { size, horizontal } => {
+                let (width, height) = if horizontal {
+                    (size, size / aspect_ratio)
+                } else {
+                    (size * aspect_ratio, size)
+                };
+                Box::new(Orthographic::new(
+                    position, look_at, up, width, height, roll,
+                ))
+            }
+            ProjectionSpec::Fisheye { angle, mapping } => Box::new(Fisheye::new(
+                position,
+                look_at,
+                up,
+                angle,
+                mapping,
+                aspect_ratio,
+                roll,
+            )),
+            ProjectionSpec::Equirectangular => {
+                Box::new(Equirectangular::new(position, look_at, up, roll))
+            }
+        }
+    }
+}
+
+// Everything described by a scene file.
+#[derive(Debug)]
+pub struct SceneFile {
+    pub width: u32,
+    pub height: u32,
+    pub environment: Environment,
+    pub options: Options,
+    pub cameras: Vec<CameraSpec>,
+    pub objects: Vec<Box<dyn Intersectable>>,
+    pub lights: Vec<Light>,
+}
+
+impl SceneFile {
+    // Read and parse the scene file at the given path. Files it refers to
+    // are found relative to the directory holding it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile, Error> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        SceneFile::parse_in(&source, directory)
+            .map_err(|err| Error::InvalidScene(path.to_path_buf(), err.to_string()))
+    }
+
+    // Parse a scene from its textual description. Files it refers to are
+    // found relative to the current directory.
+    pub fn parse(source: &str) -> Result<SceneFile, ParseError> {
+        SceneFile::parse_in(source, Path::new(""))
+    }
+
+    // Parse a scene from its textual description, finding the files it refers
+    // to relative to 'directory'.
+    pub fn parse_in(source: &str, directory: &Path) -> Result<SceneFile, ParseError> {
+        Parser::new(source, directory).scene_file()
+    }
+
+    // Return the names of the cameras defined in the file, in order.
+    pub fn camera_names(&self) -> Vec<&str> {
+        self.cameras
+            .iter()
+            .map(|camera| camera.name.as_str())
+            .collect()
+    }
+
+    // Build the scene as seen through the named camera, or through the first
+    // camera when no name is given. Returns None for an unknown camera name.
+    pub fn into_scene(self, camera: Option<&str>) -> Option<Scene> {
+        let spec = match camera {
+            Some(name) => self.cameras.iter().find(|spec| spec.name == name)?,
+            None => self.cameras.first()?,
+        };
+        let aspect_ratio = self.width as f64 / self.height as f64;
+
+        Some(Scene {
+            width: self.width,
+            height: self.height,
+            camera: spec.build(aspect_ratio),
+            objects: self.objects,
+            lights: self.lights,
+            environment: self.environment,
+            options: self.options,
+        })
+    }
+}
+
+// A word or brace in the source, along with where it starts.
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    end: (usize, usize), // Line and column just past the last character
+    directory: PathBuf,  // Directory relative paths start from
+    models: HashMap<(PathBuf, bool), Vec<obj::ObjMesh>>, // Models already loaded
+    images: HashMap<(PathBuf, bool), ImageTexture>, // Images already loaded
+}
+
+impl Parser {
+    fn new(source: &str, directory: &Path) -> Parser {
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut column = 1;
+        let mut current: Option<Token> = None;
+        let mut in_comment = false;
+
+        for c in source.chars() {
+            let separator = c.is_whitespace() || c == '#' || c == '{' || c == '}';
+            if in_comment || separator {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+
+            if c == '\n' {
+                in_comment = false;
+            } else if !in_comment {
+                if c == '#' {
+                    in_comment = true;
+                } else if c == '{' || c == '}' {
+                    tokens.push(Token {
+                        text: c.to_string(),
+                        line,
+                        column,
+                    });
+                } else if !c.is_whitespace() {
+                    current
+                        .get_or_insert_with(|| Token {
+                            text: String::new(),
+                            line,
+                            column,
+                        })
+                        .text
+                        .push(c);
+                }
+            }
+
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+
+        if let Some(token) = current.take() {
+            tokens.push(token);
+        }
+
+        Parser {
+            tokens,
+            position: 0,
+            end: (line, column),
+            directory: directory.to_path_buf(),
+            models: HashMap::new(),
+            images: HashMap::new(),
+        }
+    }
+
+    fn error(token: &Token, message: String) -> ParseError {
+        ParseError {
+            line: token.line,
+            column: token.column,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(ParseError {
+                line: self.end.0,
+                column: self.end.1,
+                message: "unexpected end of file".to_string(),
+            }),
+        }
+    }
+
+    // Consume the next token if it has the given text.
+    fn accept(&mut self, text: &str) -> bool {
+        if self.peek().is_some_and(|token| token.text == text) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, ParseError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(token)
+        } else {
+            Err(Parser::error(
+                &token,
+                format!("expected '{}', found '{}'", text, token.text),
+            ))
+        }
+    }
+
+    // Read a name, rejecting braces.
+    fn word(&mut self) -> Result<Token, ParseError> {
+        let token = self.next()?;
+        if token.text == "{" || token.text == "}" {
+            Err(Parser::error(
+                &token,
+                format!("expected a name, found '{}'", token.text),
+            ))
+        } else {
+            Ok(token)
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ParseError> {
+        let token = self.next()?;
+        match token.text.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(Parser::error(
+                &token,
+                format!("expected a number, found '{}'", token.text),
+            )),
+        }
+    }
+
+    fn positive_number(&mut self) -> Result<f64, ParseError> {
+        let token = self.peek().cloned();
+        let value = self.number()?;
+        if value > 0.0 {
+            Ok(value)
+        } else {
+            Err(Parser::error(
+                &token.unwrap(),
+                format!("expected a positive number, found {}", value),
+            ))
+        }
+    }
+
+    fn integer<T: std::str::FromStr>(&mut self) -> Result<T, ParseError> {
+        let token = self.next()?;
+        token.text.parse::<T>().map_err(|_| {
+            Parser::error(
+                &token,
+                format!("expected a whole number, found '{}'", token.text),
+            )
+        })
+    }
+
+    fn boolean(&mut self) -> Result<bool, ParseError> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            "true" | "on" | "yes" => Ok(true),
+            "false" | "off" | "no" => Ok(false),
+            _ => Err(Parser::error(
+                &token,
+                format!("expected true or false, found '{}'", token.text),
+            )),
+        }
+    }
+
+    // Parse one of the names accepted by the type, such as a sampler.
+    fn choice<T: std::str::FromStr<Err = String>>(&mut self) -> Result<T, ParseError> {
+        let token = self.next()?;
+        token.text.parse().map_err(|expected| {
+            Parser::error(&token, format!("{}, found '{}'", expected, token.text))
+        })
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, ParseError> {
+        Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    // Read a direction, rejecting the zero vector.
+    fn direction(&mut self) -> Result<Vec3, ParseError> {
+        let token = self.peek().cloned();
+        let value = self.vec3()?;
+        if value.length() > crate::EPSILON {
+            Ok(value)
+        } else {
+            Err(Parser::error(
+                &token.unwrap(),
+                "a direction must not be zero".to_string(),
+            ))
+        }
+    }
+
+    // Read a color given by name, as three numbers between 0 and 1, or as
+    // "rgb" followed by three numbers between 0 and 255.
+    fn color(&mut self) -> Result<Color, ParseError> {
+        let token = self.next()?;
+        let color = match token.text.as_str() {
+            "white" => Color::white(),
+            "black" => Color::black(),
+            "purple" => Color::purple(),
+            "red" => Color::red(),
+            "green" => Color::green(),
+            "blue" => Color::blue(),
+            "yellow" => Color::yellow(),
+            "cyan" => Color::cyan(),
+            "pink" => Color::pink(),
+            "gray" | "grey" => Color::gray(),
+            "orange" => Color::orange(),
+            "rgb" => Color::from_u8(self.integer()?, self.integer()?, self.integer()?),
+            text => match text.parse::<f64>() {
+                Ok(r) => Color::new(r, self.number()?, self.number()?),
+                Err(_) => {
+                    return Err(Parser::error(
+                        &token,
+                        format!("expected a color, found '{}'", text),
+                    ))
+                }
+            },
+        };
+        Ok(color)
+    }
+
+    // Parse a "{ ... }" block, handing each property name to the callback
+    // which is responsible for reading the property's values.
+    fn block<F>(&mut self, mut property: F) -> Result<Token, ParseError>
+    where
+        F: FnMut(&mut Parser, &Token) -> Result<(), ParseError>,
+    {
+        let open = self.expect("{")?;
+        loop {
+            if self.accept("}") {
+                return Ok(open);
+            }
+            let key = self.word()?;
+            property(self, &key)?;
+        }
+    }
+
+    fn unknown(key: &Token, item: &str) -> ParseError {
+        Parser::error(key, format!("unknown property '{}' in {}", key.text, item))
+    }
+
+    fn required<T>(
+        value: Option<T>,
+        open: &Token,
+        item: &str,
+        name: &str,
+    ) -> Result<T, ParseError> {
+        value.ok_or_else(|| Parser::error(open, format!("{} is missing '{}'", item, name)))
+    }
+
+    fn scene_file(&mut self) -> Result<SceneFile, ParseError> {
+        let mut file = SceneFile {
+            width: 1920,
+            height: 1080,
+            environment: Environment::Uniform(Color::black()),
+            options: Options::default(),
+            cameras: Vec::new(),
+            objects: Vec::new(),
+            lights: Vec::new(),
+        };
+
+        while self.peek().is_some() {
+            let item = self.word()?;
+            match item.text.as_str() {
+                "width" => file.width = self.dimension()?,
+                "height" => file.height = self.dimension()?,
+                "background" => file.environment = Environment::Uniform(self.color()?),
+                "environment" => file.environment = self.environment()?,
+                "options" => self.options(&mut file.options)?,
+                "camera" => {
+                    let camera = self.camera(file.cameras.len())?;
+                    if file.cameras.iter().any(|other| other.name == camera.name) {
+                        return Err(Parser::error(
+                            &item,
+                            format!("duplicate camera '{}'", camera.name),
+                        ));
+                    }
+                    file.cameras.push(camera);
+                }
+                "light" => file.lights.push(self.light()?),
+                "sphere" | "plane" | "cube" | "cylinder" => file.objects.push(self.object(&item)?),
+                "triangle" => file.objects.push(self.triangle()?),
+                "mesh" => file.objects.push(self.mesh()?),
+                "obj" => file.objects.extend(self.obj()?),
+                text => return Err(Parser::error(&item, format!("unknown item '{}'", text))),
+            }
+        }
+
+        if file.cameras.is_empty() {
+            return Err(ParseError {
+                line: self.end.0,
+                column: self.end.1,
+                message: "the scene does not define a camera".to_string(),
+            });
+        }
+
+        Ok(file)
+    }
+
+    fn dimension(&mut self) -> Result<u32, ParseError> {
+        self.nonzero("image dimensions")
+    }
+
+    // Parse a whole number which must not be zero.
+    fn nonzero(&mut self, what: &str) -> Result<u32, ParseError> {
+        let token = self.peek().cloned();
+        let value: u32 = self.integer()?;
+        if value == 0 {
+            return Err(Parser::error(
+                &token.unwrap(),
+                format!("{} must not be zero", what),
+            ));
+        }
+        Ok(value)
+    }
+
+    fn options(&mut self, options: &mut Options) -> Result<(), ParseError> {
+        self.block(|parser, key| {
+            match key.text.as_str() {
+                "max_rays" => options.max_rays = parser.integer()?,
+                "gamma" => options.gamma = parser.positive_number()?,
+                "exposure" => options.exposure = parser.number()?,
+                "tone_map" => options.tone_map = parser.choice()?,
+                "white_point" => options.white_point = Some(parser.positive_number()?),
+                "transfer" => options.transfer = parser.choice()?,
+                "diffuse" => options.diffuse = parser.boolean()?,
+                "specular" => options.specular = parser.boolean()?,
+                "shadows" => options.shadows = parser.boolean()?,
+                "reflections" => options.reflections = parser.boolean()?,
+                "refractions" => options.refractions = parser.boolean()?,
+                "threads" => options.threads = parser.integer()?,
+                "samples" => options.samples = parser.nonzero("samples")?,
+                "sampler" => options.sampler = parser.choice()?,
+                "filter" => options.filter = parser.choice()?,
+                "seed" => options.seed = parser.integer()?,
+                "integrator" => options.integrator = parser.choice()?,
+                "format" => options.format = Some(parser.choice()?),
+                "exr_precision" => options.exr_precision = parser.choice()?,
+                "exr_compression" => options.exr_compression = parser.choice()?,
+                _ => return Err(Parser::unknown(key, "options")),
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn environment(&mut self) -> Result<Environment, ParseError> {
+        let kind = self.word()?;
+        let item = format!("{} environment", kind.text);
+        let mut zenith = Color::new(0.25, 0.45, 0.8);
+        let mut horizon = Color::new(0.8, 0.85, 0.9);
+        let mut ground = None;
+        let mut direction = None;
+        let mut turbidity = 3.0;
+        let mut intensity = None;
+        let mut sun_size = 0.53;
+        let mut sun_intensity = 1.0;
+        let mut sun_color = Color::new(1.0, 0.95, 0.85);
+        let mut file = None;
+        let mut rotation = 0.0;
+
+        let open = self.block(|parser, key| {
+            match (kind.text.as_str(), key.text.as_str()) {
+                ("gradient", "zenith") => zenith = parser.color()?,
+                ("gradient", "horizon") => horizon = parser.color()?,
+                ("gradient" | "sky", "ground") => ground = Some(parser.color()?),
+                ("sky", "direction") => direction = Some(parser.direction()?),
+                ("sky", "turbidity") => {
+                    let token = parser.peek().cloned();
+                    turbidity = parser.number()?;
+                    if !(1.7..=10.0).contains(&turbidity) {
+                        return Err(Parser::error(
+                            &token.unwrap(),
+                            "the turbidity must be from 1.7 up to 10".to_string(),
+                        ));
+                    }
+                }
+                ("sky" | "map", "intensity") => intensity = Some(parser.number()?),
+                // The angular diameter of the sun, in degrees. 0 leaves it out.
+                ("sky", "sun_size") => {
+                    let token = parser.peek().cloned();
+                    sun_size = parser.number()?;
+                    if !(0.0..180.0).contains(&sun_size) {
+                        return Err(Parser::error(
+                            &token.unwrap(),
+                            "the sun size must be from 0 up to 180 degrees".to_string(),
+                        ));
+                    }
+                }
+                ("sky", "sun_intensity") => sun_intensity = parser.number()?,
+                ("sky", "sun_color") => sun_color = parser.color()?,
+                ("map", "file") => file = Some(parser.word()?),
+                // Turn around the vertical axis, in degrees.
+                ("map", "rotation") => rotation = parser.number()?,
+                _ => return Err(Parser::unknown(key, &item)),
+            }
+            Ok(())
+        })?;
+
+        match kind.text.as_str() {
+            "gradient" => Ok(Environment::Gradient {
+                zenith,
+                horizon,
+                ground: ground.unwrap_or(Color::new(0.3, 0.3, 0.3)),
+            }),
+            "sky" => Ok(Environment::Sky(Box::new(Sky::new(
+                Parser::required(direction, &open, &item, "direction")?,
+                turbidity,
+                intensity.unwrap_or(0.3),
+                sun_size,
+                sun_intensity,
+                sun_color,
+                ground.unwrap_or(Color::new(0.1, 0.1, 0.1)),
+            )))),
+            "map" => {
+                let file = Parser::required(file, &open, &item, "file")?;
+                let path = self.directory.join(&file.text);
+                let mut map = EnvironmentMap::open(&path).map_err(|err| {
+                    Parser::error(&file, format!("cannot load '{}': {}", path.display(), err))
+                })?;
+                map.rotation = rotation.to_radians();
+                map.intensity = intensity.unwrap_or(1.0);
+                Ok(Environment::Map(map))
+            }
+            text => Err(Parser::error(
+                &kind,
+                format!(
+                    "unknown environment '{}', expected gradient, sky or map",
+                    text
+                ),
+            )),
+        }
+    }
+
+    fn camera(&mut self, index: usize) -> Result<CameraSpec, ParseError> {
+        // The name is optional, unnamed cameras are numbered from zero.
+        let name = match self.peek() {
+            Some(token) if token.text != "{" => self.word()?.text,
+            _ => index.to_string(),
+        };
+        let mut position = None;
+        let mut look_at = None;
+        let mut up = Vec3::new(0.0, 1.0, 0.0);
+        let mut roll = 0.0;
+        let mut projection = None;
+
+        // Settings which only some projections have, along with their key.
+        let mut fov = None;
+        let mut size = None;
+        let mut mapping = None;
+        let mut aperture = None;
+        let mut focus_distance = None;
+
+        let open = self.block(|parser, key| {
+            match key.text.as_str() {
+                "position" => position = Some(parser.vec3()?),
+                "look_at" => look_at = Some(parser.vec3()?),
+                "up" => up = parser.direction()?,
+                "roll" => roll = parser.number()?,
+                "projection" => projection = Some(parser.word()?),
+                "fov" => fov = Some((key.clone(), parser.along_axis()?)),
+                "size" => size = Some((key.clone(), parser.along_axis()?)),
+                "mapping" => mapping = Some((key.clone(), parser.choice()?)),
+                "aperture" => aperture = Some((key.clone(), parser.number()?)),
+                "focus_distance" => focus_distance = Some((key.clone(), parser.positive_number()?)),
+                _ => return Err(Parser::unknown(key, "camera")),
+            }
+            Ok(())
+        })?;
+
+        let position = Parser::required(position, &open, "camera", "position")?;
+        let look_at = Parser::required(look_at, &open, "camera", "look_at")?;
+        if (position - look_at).length() < crate::EPSILON {
+            return Err(Parser::error(
+                &open,
+                "camera position and look_at must differ".to_string(),
+            ));
+        }
+        if up
+            .normalize()
+            .cross((look_at - position).normalize())
+            .length()
+            < crate::EPSILON
+        {
+            return Err(Parser::error(
+                &open,
+                "camera up must not be parallel to the view direction".to_string(),
+            ));
+        }
+
+        let kind = projection
+            .as_ref()
+            .map_or("perspective", |token| token.text.as_str());
+
+        // Reject the settings the projection does not have.
+        let settings = [
+            (fov.as_ref().map(|(key, _)| key), "perspective fisheye"),
+            (size.as_ref().map(|(key, _)| key), "orthographic"),
+            (mapping.as_ref().map(|(key, _)| key), "fisheye"),
+            (aperture.as_ref().map(|(key, _)| key), "perspective"),
+            (focus_distance.as_ref().map(|(key, _)| key), "perspective"),
+        ];
+        for (key, kinds) in settings {
+            if let Some(key) = key.filter(|_| !kinds.split(' ').any(|allowed| allowed == kind)) {
+                return Err(Parser::error(
+                    key,
+                    format!("'{}' does not apply to {} cameras", key.text, kind),
+                ));
+            }
+        }
+
+        let projection = match kind {
+            "perspective" => {
+                let fov = match fov {
+                    Some((key, (angle, _))) if angle >= 180.0 => {
+                        return Err(Parser::error(
+                            &key,
+                            "the field of view must be less than 180 degrees".to_string(),
+                        ))
+                    }
+                    Some((_, (angle, true))) => Fov::Horizontal(angle),
+                    Some((_, (angle, false))) => Fov::Vertical(angle),
+                    None => Fov::Vertical(60.0),
+                };
+                let aperture = match aperture {
+                    Some((key, aperture)) if aperture < 0.0 => {
+                        return Err(Parser::error(
+                            &key,
+                            "camera aperture must not be negative".to_string(),
+                        ))
+                    }
+                    Some((_, aperture)) => aperture,
+                    None => 0.0,
+                };
+                ProjectionSpec::Perspective {
+                    fov,
+                    aperture,
+                    focus_distance: focus_distance.map(|(_, distance)| distance),
+                }
+            }
+            "orthographic" => {
+                let (_, (size, horizontal)) =
+                    Parser::required(size, &open, "orthographic camera", "size")?;
+                ProjectionSpec::Orthographic { size, horizontal }
+            }
+            "fisheye" => {
+                let angle = match fov {
+                    Some((key, (angle, _))) if angle > 360.0 => {
+                        return Err(Parser::error(
+                            &key,
+                            "a fisheye sees at most 360 degrees".to_string(),
+                        ))
+                    }
+                    Some((_, (angle, _))) => angle,
+                    None => 180.0,
+                };
+                ProjectionSpec::Fisheye {
+                    angle,
+                    mapping: mapping.map_or(FisheyeMapping::Equidistant, |(_, mapping)| mapping),
+                }
+            }
+            "equirectangular" => ProjectionSpec::Equirectangular,
+            _ => {
+                let token = projection.unwrap();
+                return Err(Parser::error(
+                    &token,
+                    format!(
+                        "unknown projection '{}', expected perspective, orthographic, fisheye or equirectangular",
+                        token.text
+                    ),
+                ));
+            }
+        };
+
+        Ok(CameraSpec {
+            name,
+            position,
+            look_at,
+            up,
+            roll,
+            projection,
+        })
+    }
+
+    // Parse a positive size or angle, optionally followed by the axis of the
+    // image it is measured along ("fov 90 horizontal"). Returns the value and
+    // whether it is horizontal, as it is vertical by default.
+    fn along_axis(&mut self) -> Result<(f64, bool), ParseError> {
+        let value = self.positive_number()?;
+        if self.accept("horizontal") {
+            Ok((value, true))
+        } else {
+            self.accept("vertical");
+            Ok((value, false))
+        }
+    }
+
+    // Parse how a light fades with distance: none, linear, inverse_square, or
+    // custom followed by its constant, linear and quadratic terms.
+    fn attenuation(&mut self) -> Result<Attenuation, ParseError> {
+        let token = self.word()?;
+        match token.text.as_str() {
+            "none" => Ok(Attenuation::None),
+            "linear" => Ok(Attenuation::Linear),
+            "inverse_square" => Ok(Attenuation::InverseSquare),
+            "custom" => {
+                let (constant, linear, quadratic) =
+                    (self.number()?, self.number()?, self.number()?);
+                if constant < 0.0 || linear < 0.0 || quadratic < 0.0 {
+                    return Err(Parser::error(
+                        &token,
+                        "the terms of a custom attenuation must not be negative".to_string(),
+                    ));
+                }
+                if constant + linear + quadratic <= 0.0 {
+                    return Err(Parser::error(
+                        &token,
+                        "a custom attenuation needs a term above zero".to_string(),
+                    ));
+                }
+                Ok(Attenuation::Custom {
+                    constant,
+                    linear,
+                    quadratic,
+                })
+            }
+            text => Err(Parser::error(
+                &token,
+                format!(
+                    "expected none, linear, inverse_square or custom, found '{}'",
+                    text
+                ),
+            )),
+        }
+    }
+
+    fn light(&mut self) -> Result<Light, ParseError> {
+        let kind = self.word()?;
+        let item = format!("{} light", kind.text);
+        let mut position = None;
+        let mut target = None;
+        let mut intensity = 1.0;
+        let mut color = Color::white();
+        let mut angle = None;
+        let mut inner_angle = None;
+        let mut attenuation = Attenuation::None;
+        let mut sides = (None, None);
+        let mut normal = None;
+        let mut radius = None;
+        let mut samples = 16;
+        let mut direction = None;
+        let mut diameter = 0.0;
+
+        let sampled = ["rectangle", "disk", "sphere", "directional"].contains(&kind.text.as_str());
+        let open = self.block(|parser, key| {
+            match (kind.text.as_str(), key.text.as_str()) {
+                (_, "intensity") => intensity = parser.number()?,
+                (_, "color") => color = parser.color()?,
+                (kind, "position") if !["ambient", "directional"].contains(&kind) => {
+                    position = Some(parser.vec3()?)
+                }
+                ("spotlight", "target") => target = Some(parser.vec3()?),
+                ("spotlight", "angle") => angle = Some(parser.positive_number()?),
+                ("spotlight", "inner_angle") => inner_angle = Some(parser.positive_number()?),
+                (kind, "attenuation") if !["ambient", "directional"].contains(&kind) => {
+                    attenuation = parser.attenuation()?
+                }
+                ("rectangle", "u") => sides.0 = Some(parser.vec3()?),
+                ("rectangle", "v") => sides.1 = Some(parser.vec3()?),
+                ("disk", "normal") => normal = Some(parser.direction()?),
+                ("disk" | "sphere", "radius") => radius = Some(parser.positive_number()?),
+                ("directional", "direction") => direction = Some(parser.direction()?),
+                // The angular diameter, in degrees, as seen from the scene.
+                ("directional", "angular_diameter") => {
+                    let token = parser.peek().cloned();
+                    diameter = parser.number()?;
+                    if !(0.0..180.0).contains(&diameter) {
+                        return Err(Parser::error(
+                            &token.unwrap(),
+                            "the angular diameter must be from 0 up to 180 degrees".to_string(),
+                        ));
+                    }
+                }
+                (_, "samples") if sampled => samples = parser.nonzero("samples")?,
+                _ => return Err(Parser::unknown(key, &item)),
+            }
+            Ok(())
+        })?;
+
+        let light = match kind.text.as_str() {
+            "ambient" => Light {
+                light_type: LightType::Ambient,
+                position: Vec3::zero(),
+                intensity,
+                color,
+                attenuation,
+            },
+            "point" => Light {
+                light_type: LightType::Point,
+                position: Parser::required(position, &open, &item, "position")?,
+                intensity,
+                color,
+                attenuation,
+            },
+            "spotlight" => {
+                // Without an inner angle the spotlight has a hard edge.
+                let angle = Parser::required(angle, &open, &item, "angle")?;
+                if inner_angle.is_some_and(|inner| inner > angle) {
+                    return Err(Parser::error(
+                        &open,
+                        "the inner angle of a spotlight must not exceed its angle".to_string(),
+                    ));
+                }
+                let position = Parser::required(position, &open, &item, "position")?;
+                let target = Parser::required(target, &open, &item, "target")?;
+                if (position - target).length() < crate::EPSILON {
+                    return Err(Parser::error(
+                        &open,
+                        "spotlight position and target must differ".to_string(),
+                    ));
+                }
+                create_spotlight(
+                    position,
+                    target,
+                    intensity,
+                    color,
+                    angle,
+                    inner_angle.unwrap_or(angle),
+                    attenuation,
+                )
+            }
+            "directional" => Light {
+                light_type: LightType::Directional {
+                    direction: Parser::required(direction, &open, &item, "direction")?,
+                    diameter: diameter.to_radians(),
+                    samples,
+                },
+                position: Vec3::zero(),
+                intensity,
+                color,
+                attenuation,
+            },
+            "rectangle" | "disk" | "sphere" => {
+                let shape = match kind.text.as_str() {
+                    "rectangle" => {
+                        let u = Parser::required(sides.0, &open, &item, "u")?;
+                        let v = Parser::required(sides.1, &open, &item, "v")?;
+                        if u.cross(v).length() <= crate::EPSILON {
+                            return Err(Parser::error(
+                                &open,
+                                "the sides of a rectangle light must not be parallel".to_string(),
+                            ));
+                        }
+                        AreaShape::Rectangle { u, v }
+                    }
+                    "disk" => AreaShape::Disk {
+                        normal: Parser::required(normal, &open, &item, "normal")?,
+                        radius: Parser::required(radius, &open, &item, "radius")?,
+                    },
+                    _ => AreaShape::Sphere {
+                        radius: Parser::required(radius, &open, &item, "radius")?,
+                    },
+                };
+                Light {
+                    light_type: LightType::Area { shape, samples },
+                    position: Parser::required(position, &open, &item, "position")?,
+                    intensity,
+                    color,
+                    attenuation,
+                }
+            }
+            text => {
+                return Err(Parser::error(
+                    &kind,
+                    format!("unknown light type '{}'", text),
+                ))
+            }
+        };
+        Ok(light)
+    }
+
+    // Read a texture if one starts here, reading the colors it blends with
+    // 'value'. Images hold colors decoded from sRGB when 'color' is set, and
+    // data used as it is stored otherwise. Returns None when the next token
+    // does not name a texture.
+    fn texture<F>(&mut self, value: F, color: bool) -> Result<Option<Arc<dyn Texture>>, ParseError>
+    where
+        F: Fn(&mut Parser) -> Result<Color, ParseError>,
+    {
+        const KINDS: [&str; 7] = [
+            "checker", "stripes", "gradient", "noise", "marble", "wood", "image",
+        ];
+        if !self
+            .peek()
+            .is_some_and(|token| KINDS.contains(&token.text.as_str()))
+        {
+            return Ok(None);
+        }
+        let kind = self.next()?;
+        let item = kind.text.as_str();
+
+        let mut first = Color::white();
+        let mut second = Color::black();
+        let mut scale = 1.0;
+        let mut axis = UvAxis::U;
+        let mut octaves = 4;
+        let mut turbulence = if item == "marble" { 5.0 } else { 0.5 };
+        let mut file = None;
+
+        let open = self.block(|parser, key| {
+            match (item, key.text.as_str()) {
+                ("checker" | "stripes", "even") => first = value(parser)?,
+                ("checker" | "stripes", "odd") => second = value(parser)?,
+                ("gradient" | "noise" | "marble" | "wood", "from") => first = value(parser)?,
+                ("gradient" | "noise" | "marble" | "wood", "to") => second = value(parser)?,
+                ("stripes" | "gradient", "axis") => axis = parser.choice()?,
+                ("noise" | "marble", "octaves") => octaves = parser.nonzero("octaves")?,
+                ("marble" | "wood", "turbulence") => turbulence = parser.number()?,
+                ("image", "file") => file = Some(parser.word()?),
+                (_, "scale") if item != "gradient" => scale = parser.positive_number()?,
+                _ => return Err(Parser::unknown(key, item)),
+            }
+            Ok(())
+        })?;
+
+        let texture: Arc<dyn Texture> = match item {
+            "checker" => Arc::new(Checker {
+                even: first,
+                odd: second,
+                scale,
+            }),
+            "stripes" => Arc::new(Stripes {
+                even: first,
+                odd: second,
+                scale,
+                axis,
+            }),
+            "gradient" => Arc::new(Gradient {
+                from: first,
+                to: second,
+                axis,
+            }),
+            "noise" => Arc::new(Noise {
+                from: first,
+                to: second,
+                scale,
+                octaves,
+            }),
+            "marble" => Arc::new(Marble {
+                from: first,
+                to: second,
+                scale,
+                turbulence,
+                octaves,
+            }),
+            "wood" => Arc::new(Wood {
+                from: first,
+                to: second,
+                scale,
+                turbulence,
+            }),
+            _ => {
+                let file = Parser::required(file, &open, item, "file")?;
+                let mut image = self.image(&file, color)?;
+                image.scale = scale;
+                Arc::new(image)
+            }
+        };
+        Ok(Some(texture))
+    }
+
+    // Read a number, or a texture whose colors stand for numbers. Returns the
+    // texture, or None after storing the number in 'value'.
+    fn scalar_texture(&mut self, value: &mut f64) -> Result<Option<Arc<dyn Texture>>, ParseError> {
+        let texture = self.texture(Parser::gray, false)?;
+        if texture.is_none() {
+            *value = self.number()?;
+        }
+        Ok(texture)
+    }
+
+    // Read a share of the light as a number from 0 to 1 or a texture, whose
+    // values are clamped to that range when shading.
+    fn fraction_texture(
+        &mut self,
+        value: &mut f64,
+        what: &str,
+    ) -> Result<Option<Arc<dyn Texture>>, ParseError> {
+        let token = self.peek().cloned();
+        let texture = self.scalar_texture(value)?;
+        if texture.is_none() && !(0.0..=1.0).contains(value) {
+            return Err(Parser::error(
+                &token.unwrap(),
+                format!("the {} must be from 0 to 1", what),
+            ));
+        }
+        Ok(texture)
+    }
+
+    // Read a number as the gray color textures use to stand for it.
+    fn gray(&mut self) -> Result<Color, ParseError> {
+        let number = self.number()?;
+        Ok(Color::new(number, number, number))
+    }
+
+    // Read a texture that must be there, for properties with no plain value.
+    fn required_texture<F>(&mut self, value: F, color: bool) -> Result<Arc<dyn Texture>, ParseError>
+    where
+        F: Fn(&mut Parser) -> Result<Color, ParseError>,
+    {
+        match self.texture(value, color)? {
+            Some(texture) => Ok(texture),
+            None => {
+                let token = self.next()?;
+                Err(Parser::error(
+                    &token,
+                    format!("expected a texture, found '{}'", token.text),
+                ))
+            }
+        }
+    }
+
+    // Load the image named by the token, relative to the scene file, as colors
+    // or as data. Images used several times are loaded once.
+    fn image(&mut self, file: &Token, color: bool) -> Result<ImageTexture, ParseError> {
+        let path = self.directory.join(&file.text);
+        if let Some(image) = self.images.get(&(path.clone(), color)) {
+            return Ok(image.clone());
+        }
+        let image = if color {
+            ImageTexture::open(&path)
+        } else {
+            ImageTexture::open_data(&path)
+        }
+        .map_err(|err| Parser::error(file, format!("cannot load '{}': {}", path.display(), err)))?;
+        self.images.insert((path, color), image.clone());
+        Ok(image)
+    }
+
+    // Read a material given as a preset name, optionally followed by a block
+    // overriding individual properties, or as a block on its own.
+    fn material(&mut self) -> Result<Material, ParseError> {
+        let mut material = if self.peek().is_some_and(|token| token.text == "{") {
+            Material::neutral()
+        } else {
+            let preset = self.word()?;
+            match preset.text.as_str() {
+                "neutral" => Material::neutral(),
+                "mirror" => Material::mirror(),
+                "pearl" => Material::pearl(),
+                "glass" => Material::glass(),
+                "water" => Material::water(),
+                "diamond" => Material::diamond(),
+                "matt" => Material::matt(self.color()?),
+                "plastic" => Material::plastic(self.color()?),
+                "emissive" => Material::emissive(self.color()?),
+                text => {
+                    return Err(Parser::error(
+                        &preset,
+                        format!("unknown material '{}'", text),
+                    ))
+                }
+            }
+        };
+
+        if self.peek().is_some_and(|token| token.text == "{") {
+            let open = self.block(|parser, key| {
+                match key.text.as_str() {
+                    "color" => match parser.texture(Parser::color, true)? {
+                        Some(texture) => material.textures.color = Some(texture),
+                        None => {
+                            material.color = parser.color()?;
+                            material.textures.color = None;
+                        }
+                    },
+                    "diffuse" => {
+                        material.textures.diffuse =
+                            parser.fraction_texture(&mut material.diffuse, "diffuse")?
+                    }
+                    "specular" => {
+                        material.textures.specular =
+                            parser.fraction_texture(&mut material.specular, "specular")?
+                    }
+                    "specular_exponent" => {
+                        let token = parser.peek().cloned();
+                        material.specular_exponent = parser.number()?;
+                        if material.specular_exponent < 0.0 {
+                            return Err(Parser::error(
+                                &token.unwrap(),
+                                "the specular exponent must not be negative".to_string(),
+                            ));
+                        }
+                    }
+                    "reflectiveness" => {
+                        material.textures.reflectiveness = parser
+                            .fraction_texture(&mut material.reflectiveness, "reflectiveness")?
+                    }
+                    "transparency" => {
+                        material.textures.transparency =
+                            parser.fraction_texture(&mut material.transparency, "transparency")?
+                    }
+                    "index_of_refraction" => {
+                        material.index_of_refraction = parser.positive_number()?
+                    }
+                    "emission" => match parser.texture(Parser::color, true)? {
+                        Some(texture) => material.textures.emission = Some(texture),
+                        None => {
+                            material.emission = parser.color()?;
+                            material.textures.emission = None;
+                        }
+                    },
+                    "emission_strength" => {
+                        let token = parser.peek().cloned();
+                        material.emission_strength = parser.number()?;
+                        if material.emission_strength < 0.0 {
+                            return Err(Parser::error(
+                                &token.unwrap(),
+                                "the emission strength must not be negative".to_string(),
+                            ));
+                        }
+                    }
+                    // Heights given as numbers, in scene units.
+                    "bump" => {
+                        material.textures.bump = Some(parser.required_texture(Parser::gray, false)?)
+                    }
+                    "normal_map" => {
+                        material.textures.normal =
+                            Some(parser.required_texture(Parser::color, false)?)
+                    }
+                    _ => return Err(Parser::unknown(key, "material")),
+                }
+                Ok(())
+            })?;
+
+            // What is reflected cannot pass through as well.
+            let textures = &material.textures;
+            if textures.reflectiveness.is_none()
+                && textures.transparency.is_none()
+                && material.reflectiveness + material.transparency > 1.0
+            {
+                return Err(Parser::error(
+                    &open,
+                    "reflectiveness and transparency must not add up to more than 1".to_string(),
+                ));
+            }
+        }
+
+        Ok(material)
+    }
+
+    // Read a transform property of an object, applying it after the ones
+    // already read. Returns false if the key is not a transform property.
+    fn transform(&mut self, key: &Token, transform: &mut Transform) -> Result<bool, ParseError> {
+        *transform = match key.text.as_str() {
+            "translate" => transform.translate(self.vec3()?),
+            // Angles in degrees around the x, then y, then z axis.
+            "rotate" => {
+                let angles = self.vec3()?;
+                transform
+                    .rotate_x(angles.x)
+                    .rotate_y(angles.y)
+                    .rotate_z(angles.z)
+            }
+            // One factor for every axis, or one factor per axis.
+            "scale" => {
+                let token = self.peek().cloned();
+                let factor = self.number()?;
+                let per_axis = self
+                    .peek()
+                    .is_some_and(|next| next.text.parse::<f64>().is_ok());
+                let factors = if per_axis {
+                    Vec3::new(factor, self.number()?, self.number()?)
+                } else {
+                    Vec3::splat(factor)
+                };
+                if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+                    return Err(Parser::error(
+                        &token.unwrap(),
+                        "scale factors must not be zero".to_string(),
+                    ));
+                }
+                transform.scale(factors)
+            }
+            _ => return Ok(false),
+        };
+        Ok(true)
+    }
+
+    // Wrap the object in the transform, unless the transform does nothing.
+    fn transformed(object: Box<dyn Intersectable>, transform: Transform) -> Box<dyn Intersectable> {
+        if transform.is_identity() {
+            object
+        } else {
+            Box::new(objects::Transformed::new(object, transform))
+        }
+    }
+
+    fn object(&mut self, kind: &Token) -> Result<Box<dyn Intersectable>, ParseError> {
+        let item = kind.text.as_str();
+        let mut position = None;
+        let mut normal = None;
+        let mut radius = None;
+        let mut height = None;
+        let mut dimensions = None;
+        let mut material = Material::matt(Color::white());
+        let mut transform = Transform::identity();
+
+        let open = self.block(|parser, key| {
+            match (item, key.text.as_str()) {
+                ("cylinder", "center") | (_, "position") => position = Some(parser.vec3()?),
+                ("plane", "normal") => normal = Some(parser.direction()?),
+                ("sphere" | "cylinder", "radius") => radius = Some(parser.positive_number()?),
+                ("cylinder", "height") => height = Some(parser.positive_number()?),
+                ("cube", "dimensions") => dimensions = Some(parser.positive_number()?),
+                (_, "material") => material = parser.material()?,
+                _ if parser.transform(key, &mut transform)? => {}
+                _ => return Err(Parser::unknown(key, item)),
+            }
+            Ok(())
+        })?;
+
+        let position_name = if item == "cylinder" {
+            "center"
+        } else {
+            "position"
+        };
+        let position = Parser::required(position, &open, item, position_name)?;
+
+        let object: Box<dyn Intersectable> = match item {
+            "sphere" => Box::new(objects::Sphere {
+                position,
+                radius: Parser::required(radius, &open, item, "radius")?,
+                material,
+            }),
+            "plane" => Box::new(objects::Plane {
+                position,
+                normal: Parser::required(normal, &open, item, "normal")?.normalize(),
+                material,
+            }),
+            "cube" => Box::new(objects::Cube {
+                position,
+                dimensions: Parser::required(dimensions, &open, item, "dimensions")?,
+                material,
+            }),
+            _ => Box::new(objects::Cylinder {
+                center: position,
+                radius: Parser::required(radius, &open, item, "radius")?,
+                height: Parser::required(height, &open, item, "height")?,
+                material,
+            }),
+        };
+        Ok(Parser::transformed(object, transform))
+    }
+
+    fn triangle(&mut self) -> Result<Box<dyn Intersectable>, ParseError> {
+        let mut vertices = [None; 3];
+        let mut material = Material::matt(Color::white());
+        let mut transform = Transform::identity();
+
+        let open = self.block(|parser, key| {
+            match key.text.as_str() {
+                "a" => vertices[0] = Some(parser.vec3()?),
+                "b" => vertices[1] = Some(parser.vec3()?),
+                "c" => vertices[2] = Some(parser.vec3()?),
+                "material" => material = parser.material()?,
+                _ if parser.transform(key, &mut transform)? => {}
+                _ => return Err(Parser::unknown(key, "triangle")),
+            }
+            Ok(())
+        })?;
+
+        let triangle = Box::new(objects::Triangle {
+            a: Parser::required(vertices[0], &open, "triangle", "a")?,
+            b: Parser::required(vertices[1], &open, "triangle", "b")?,
+            c: Parser::required(vertices[2], &open, "triangle", "c")?,
+            material,
+        });
+        Ok(Parser::transformed(triangle, transform))
+    }
+
+    // Read a mesh given by its vertices and the indices of the vertices of
+    // each face, starting from zero.
+    fn mesh(&mut self) -> Result<Box<dyn Intersectable>, ParseError> {
+        let mut positions = Vec::new();
+        let mut faces: Vec<(Token, [usize; 3])> = Vec::new();
+        let mut smooth = false;
+        let mut material = Material::matt(Color::white());
+        let mut transform = Transform::identity();
+
+        let open = self.block(|parser, key| {
+            match key.text.as_str() {
+                "vertex" => positions.push(parser.vec3()?),
+                "face" => faces.push((
+                    key.clone(),
+                    [parser.integer()?, parser.integer()?, parser.integer()?],
+                )),
+                "smooth" => smooth = parser.boolean()?,
+                "material" => material = parser.material()?,
+                _ if parser.transform(key, &mut transform)? => {}
+                _ => return Err(Parser::unknown(key, "mesh")),
+            }
+            Ok(())
+        })?;
+
+        if faces.is_empty() {
+            return Err(Parser::error(&open, "mesh has no faces".to_string()));
+        }
+        for (token, face) in &faces {
+            if let Some(index) = face.iter().find(|&&index| index >= positions.len()) {
+                return Err(Parser::error(
+                    token,
+                    format!(
+                        "face uses vertex {} but the mesh has {} vertices",
+                        index,
+                        positions.len()
+                    ),
+                ));
+            }
+        }
+
+        let triangles = faces.into_iter().map(|(_, face)| face).collect();
+        let mesh = objects::Mesh::new(positions, triangles, material);
+        let mesh = Box::new(if smooth { mesh.smooth() } else { mesh });
+        Ok(Parser::transformed(mesh, transform))
+    }
+
+    // Read a model from a Wavefront OBJ file. The materials of the file are
+    // used unless a material is given, which then applies to the whole model.
+    // A model used several times is loaded once, and its copies share their
+    // geometry.
+    fn obj(&mut self) -> Result<Vec<Box<dyn Intersectable>>, ParseError> {
+        let mut file = None;
+        let mut groups: Vec<Token> = Vec::new();
+        let mut smooth = false;
+        let mut material = None;
+        let mut transform = Transform::identity();
+
+        let open = self.block(|parser, key| {
+            match key.text.as_str() {
+                "file" => file = Some(parser.word()?),
+                "group" => groups.push(parser.word()?),
+                "smooth" => smooth = parser.boolean()?,
+                "material" => material = Some(parser.material()?),
+                _ if parser.transform(key, &mut transform)? => {}
+                _ => return Err(Parser::unknown(key, "obj")),
+            }
+            Ok(())
+        })?;
+
+        let file = Parser::required(file, &open, "obj", "file")?;
+        let path = self.directory.join(&file.text);
+        let meshes = match self.models.get(&(path.clone(), smooth)) {
+            Some(meshes) => meshes.clone(),
+            None => {
+                // Faces without a material get the given one below anyway.
+                let meshes = obj::load_obj(&path, Material::matt(Color::white()), smooth)
+                    .map_err(|err| Parser::error(&file, err.to_string()))?;
+                self.models.insert((path, smooth), meshes.clone());
+                meshes
+            }
+        };
+
+        if let Some(group) = groups
+            .iter()
+            .find(|group| !meshes.iter().any(|mesh| mesh.group == group.text))
+        {
+            return Err(Parser::error(
+                group,
+                format!("the model has no group named '{}'", group.text),
+            ));
+        }
+
+        Ok(meshes
+            .into_iter()
+            .filter(|mesh| groups.is_empty() || groups.iter().any(|group| group.text == mesh.group))
+            .map(|obj_mesh| {
+                let mut mesh = obj_mesh.mesh;
+                if let Some(material) = &material {
+                    mesh = mesh.with_material(material.clone());
+                }
+                Parser::transformed(Box::new(mesh), transform)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "camera main { position 0 0 0 look_at 0 0 -1 }\n";
+
+    // Parse 'source' after a camera, so only the lines under test matter.
+    // The camera takes the first line.
+    fn parse(source: &str) -> Result<SceneFile, ParseError> {
+        SceneFile::parse(&format!("{}{}", CAMERA, source))
+    }
+
+    // Return the line, column and message of the error 'source' gives.
+    fn error(source: &str) -> (usize, usize, String) {
+        let err = parse(source).expect_err("the scene should be rejected");
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn settings_and_options() {
+        let file = parse(
+            "width 320 height 200 # a comment { not parsed }\n\
+             background 0.1 0.2 0.3\n\
+             options { samples 4 sampler halton shadows off tone_map aces exposure -1 }\n",
+        )
+        .unwrap();
+        assert_eq!((file.width, file.height), (320, 200));
+        assert_eq!(file.options.samples, 4);
+        assert_eq!(file.options.sampler, crate::logic::Sampler::Halton);
+        assert!(!file.options.shadows);
+        assert_eq!(file.options.tone_map, crate::logic::ToneMap::Aces);
+        assert_eq!(file.options.exposure, -1.0);
+        assert_eq!(file.camera_names(), vec!["main"]);
+    }
+
+    #[test]
+    fn objects_and_materials() {
+        let file = parse(
+            "sphere { position 1 2 3 radius 2 material plastic red }\n\
+             plane { position 0 -1 0 normal 0 2 0 material { color 0.5 0.5 0.5 diffuse 0.7 } }\n\
+             cube { position 0 0 -5 dimensions 1 material mirror }\n",
+        )
+        .unwrap();
+        assert_eq!(file.objects.len(), 3);
+        let matte = file.objects[1].material();
+        assert_eq!(matte.diffuse, 0.7);
+        assert_eq!(matte.color, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(file.objects[2].material().reflectiveness, 1.0);
+    }
+
+    #[test]
+    fn lights() {
+        let file = parse(
+            "light ambient { intensity 0.2 }\n\
+             light point { position 0 5 0 intensity 0.8 color 1 0.9 0.8 }\n\
+             light spotlight { position 0 5 0 target 0 0 0 angle 30 inner_angle 20 }\n",
+        )
+        .unwrap();
+        assert_eq!(file.lights.len(), 3);
+        assert!(matches!(file.lights[0].light_type, LightType::Ambient));
+        assert_eq!(file.lights[1].position, Vec3::new(0.0, 5.0, 0.0));
+        match file.lights[2].light_type {
+            LightType::Spotlight {
+                direction,
+                angle,
+                inner_angle,
+            } => {
+                // The direction is stored pointing back towards the light.
+                assert!((direction - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+                assert!((angle - 30f64.to_radians()).abs() < 1e-9);
+                assert!((inner_angle - 20f64.to_radians()).abs() < 1e-9);
+            }
+            _ => panic!("expected a spotlight"),
+        }
+    }
+
+    #[test]
+    fn cameras() {
+        let file = SceneFile::parse(
+            "camera front { position 0 0 5 look_at 0 0 0 fov 60 }\n\
+             camera top { position 0 5 0 look_at 0 0 0 up 0 0 -1 }\n",
+        )
+        .unwrap();
+        assert_eq!(file.camera_names(), vec!["front", "top"]);
+        assert!(file.into_scene(Some("side")).is_none());
+    }
+
+    #[test]
+    fn unknown_items_and_properties() {
+        assert_eq!(
+            error("width 10\n  bogus\n"),
+            (3, 3, "unknown item 'bogus'".to_string())
+        );
+        assert_eq!(
+            error("sphere { radius 1 colour 1 0 0 }"),
+            (2, 19, "unknown property 'colour' in sphere".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            error("width ten"),
+            (2, 7, "expected a whole number, found 'ten'".to_string())
+        );
+        assert_eq!(
+            error("sphere { radius -1 }"),
+            (2, 17, "expected a positive number, found -1".to_string())
+        );
+        assert_eq!(
+            error("sphere { material { transparency 2 } }"),
+            (2, 34, "the transparency must be from 0 to 1".to_string())
+        );
+        assert_eq!(
+            error("sphere { material { specular_exponent -2 } }"),
+            (
+                2,
+                39,
+                "the specular exponent must not be negative".to_string()
+            )
+        );
+        assert_eq!(
+            error("sphere { material { reflectiveness 0.6 transparency 0.6 } }"),
+            (
+                2,
+                19,
+                "reflectiveness and transparency must not add up to more than 1".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_and_conflicting_properties() {
+        assert_eq!(
+            error("light point { intensity 1 }"),
+            (2, 13, "point light is missing 'position'".to_string())
+        );
+        assert_eq!(
+            error("light spotlight { position 1 2 3 target 1 2 3 angle 20 }"),
+            (
+                2,
+                17,
+                "spotlight position and target must differ".to_string()
+            )
+        );
+        assert_eq!(
+            error("camera main { position 0 0 0 look_at 1 0 0 }"),
+            (2, 1, "duplicate camera 'main'".to_string())
+        );
+    }
+
+    #[test]
+    fn end_of_file() {
+        assert_eq!(
+            error("sphere { radius 1"),
+            (2, 18, "unexpected end of file".to_string())
+        );
+        let err = SceneFile::parse("width 10\n").expect_err("a camera is required");
+        assert_eq!(
+            (err.line, err.column, err.message.as_str()),
+            (2, 1, "the scene does not define a camera")
+        );
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let directory = std::env::temp_dir().join(format!("rt-parser-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("empty.ppm"), "P3\n0 0\n255\n").unwrap();
+        std::fs::write(
+            directory.join("empty.hdr"),
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 0 +X 0\n",
+        )
+        .unwrap();
+        let error = |source: &str| {
+            let source = format!("{}{}\n", CAMERA, source);
+            let err = SceneFile::parse_in(&source, &directory).expect_err("the image is empty");
+            (
+                err.line,
+                err.column,
+                err.message.ends_with("the image has no pixels"),
+            )
+        };
+
+        let texture = error("sphere { radius 1 material { color image { file empty.ppm } } }");
+        let sky = error("environment map { file empty.hdr }");
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(texture, (2, 49, true));
+        assert_eq!(sky, (2, 24, true));
+    }
+}
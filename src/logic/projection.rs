@@ -0,0 +1,197 @@
+use crate::logic::camera::basis;
+use crate::logic::Ray;
+use crate::logic::Vec3;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+// Define a trait named 'Projection' for cameras, which turn points of the
+// image into rays leaving the camera. Cameras are shared between the
+// rendering threads, so they must be Send and Sync.
+pub trait Projection: Debug + Send + Sync {
+    // This method generates the ray seen at the point (s, t) of the image,
+    // both between 0 and 1 from the top left corner. Points the camera does
+    // not see, such as the corners of a circular fisheye image, give None.
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+
+    // This method generates the ray seen at the point (s, t) of the image
+    // through a point of the lens, placed by two numbers between 0 and 1.
+    // Cameras without a lens ignore it.
+    fn get_lens_ray(&self, s: f64, t: f64, _lens: (f64, f64)) -> Option<Ray> {
+        self.get_ray(s, t)
+    }
+
+    // This method returns the diameter of the lens, zero when everything is
+    // in focus.
+    fn aperture(&self) -> f64 {
+        0.0
+    }
+}
+
+// Define a camera whose rays are all parallel to the view direction, so
+// objects keep their size whatever their distance, as on architectural plans.
+#[derive(Debug)]
+pub struct Orthographic {
+    corner: Vec3,     // The top left corner of the view plane
+    horizontal: Vec3, // The horizontal vector of the view plane
+    vertical: Vec3,   // The vertical vector of the view plane
+    direction: Vec3,  // The direction of every ray
+}
+
+impl Orthographic {
+    // Create an orthographic camera seeing a 'width' by 'height' rectangle
+    // centered on its position, perpendicular to the view.
+    pub fn new(
+        origin: Vec3,
+        look_at: Vec3,
+        up: Vec3,
+        width: f64,
+        height: f64,
+        roll: f64,
+    ) -> Orthographic {
+        let (u, v, w) = basis(origin, look_at, up, roll);
+
+        Orthographic {
+            corner: origin - (u * (width / 2.0)) + (v * (height / 2.0)),
+            horizontal: u * width,
+            vertical: -v * height,
+            direction: -w,
+        }
+    }
+}
+
+impl Projection for Orthographic {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        Some(Ray {
+            origin: self.corner + (self.horizontal * s) + (self.vertical * t),
+            direction: self.direction,
+        })
+    }
+}
+
+// Define how a fisheye lens spreads the angles it sees over the image circle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    Equidistant, // The distance from the center grows with the angle
+    Equisolid,   // Every part of the circle covers the same solid angle
+}
+
+impl FromStr for FisheyeMapping {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<FisheyeMapping, String> {
+        match text {
+            "equidistant" => Ok(FisheyeMapping::Equidistant),
+            "equisolid" => Ok(FisheyeMapping::Equisolid),
+            _ => Err("expected equidistant or equisolid".to_string()),
+        }
+    }
+}
+
+// Define a camera seeing a wide angle through a circular fisheye lens. The
+// image circle touches the shorter sides of the image, and the rest of the
+// image stays black.
+#[derive(Debug)]
+pub struct Fisheye {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    half_angle: f64,         // Half the angle seen across the circle, in radians
+    mapping: FisheyeMapping, // How angles are spread over the circle
+    aspect_ratio: f64,       // Width of the image divided by its height
+}
+
+impl Fisheye {
+    // Create a fisheye camera seeing 'angle' degrees across the image circle,
+    // up to 360.
+    pub fn new(
+        origin: Vec3,
+        look_at: Vec3,
+        up: Vec3,
+        angle: f64,
+        mapping: FisheyeMapping,
+        aspect_ratio: f64,
+        roll: f64,
+    ) -> Fisheye {
+        let (u, v, w) = basis(origin, look_at, up, roll);
+
+        Fisheye {
+            origin,
+            u,
+            v,
+            w,
+            half_angle: angle.to_radians() / 2.0,
+            mapping,
+            aspect_ratio,
+        }
+    }
+}
+
+impl Projection for Fisheye {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        // Place the point relative to the image circle, of radius 1.
+        let mut x = 2.0 * s - 1.0;
+        let mut y = 1.0 - 2.0 * t;
+        if self.aspect_ratio >= 1.0 {
+            x *= self.aspect_ratio;
+        } else {
+            y /= self.aspect_ratio;
+        }
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+
+        // Find the angle between the view and the ray.
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => radius * self.half_angle,
+            FisheyeMapping::Equisolid => {
+                2.0 * (radius * (self.half_angle / 2.0).sin())
+                    .clamp(-1.0, 1.0)
+                    .asin()
+            }
+        };
+        let phi = y.atan2(x);
+
+        let sideways = (self.u * phi.cos()) + (self.v * phi.sin());
+        Some(Ray {
+            origin: self.origin,
+            direction: ((-self.w * theta.cos()) + (sideways * theta.sin())).normalize(),
+        })
+    }
+}
+
+// Define a camera seeing every direction at once, mapped to the image by
+// longitude (across) and latitude (down), as used for environment maps and
+// 360 degree panoramas. The center of the image looks at 'look_at', and the
+// image should be twice as wide as it is high.
+#[derive(Debug)]
+pub struct Equirectangular {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Equirectangular {
+    pub fn new(origin: Vec3, look_at: Vec3, up: Vec3, roll: f64) -> Equirectangular {
+        let (u, v, w) = basis(origin, look_at, up, roll);
+        Equirectangular { origin, u, v, w }
+    }
+}
+
+impl Projection for Equirectangular {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - t) * PI;
+
+        let direction = (-self.w * (latitude.cos() * longitude.cos()))
+            + (self.u * (latitude.cos() * longitude.sin()))
+            + (self.v * latitude.sin());
+        Some(Ray {
+            origin: self.origin,
+            direction: direction.normalize(),
+        })
+    }
+}
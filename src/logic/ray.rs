@@ -1,10 +1,12 @@
 // Import necessary modules and types.
 use crate::logic::Color;
-use crate::logic::Intersectable;
+use crate::logic::Environment;
 use crate::logic::Light;
 use crate::logic::Material;
 use crate::logic::Options;
+use crate::logic::Rng;
 use crate::logic::Vec3;
+use crate::logic::World;
 
 // Define a struct representing a ray with an origin and direction.
 #[derive(Clone, Copy, Debug)]
@@ -14,79 +16,226 @@ pub struct Ray {
 }
 
 // Define a struct representing information about an intersection.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Intersection {
-    pub distance: f64,      // Distance to the intersection point
-    pub hit_point: Vec3,    // Coordinates of the intersection point
-    pub normal: Vec3,       // Normal vector at the intersection point
-    pub material: Material, // Material properties of the intersected object
+    pub distance: f64,          // Distance to the intersection point
+    pub hit_point: Vec3,        // Coordinates of the intersection point
+    pub normal: Vec3,           // Normal vector used for shading at the intersection point
+    pub geometric_normal: Vec3, // Normal vector of the surface itself
+    pub material: Material,     // Material properties of the intersected object
+    pub front_face: bool,       // Whether the ray hit the object from the outside
+    pub uv: (f64, f64),         // Texture coordinates of the intersection point
+    pub object: usize,          // Index of the intersected object in the scene
 }
 
 impl Ray {
-    // Calculate the intersection of a ray with a list of objects and return information
-    // about the closest intersection, if any.
-    pub fn intersect(ray: Ray, objects: &[Box<dyn Intersectable>]) -> Option<Intersection> {
-        let mut distance = f64::INFINITY; // Initialize with positive infinity
-        let mut material = Material::neutral(); // Neutral material properties
-        let mut normal = Vec3::zero(); // Zero vector for normal
-        let mut hit_point = Vec3::zero(); // Zero vector for hit point
-
-        // Loop through the objects to find the closest intersection.
-        for shape in objects {
-            if let Some(dist) = shape.intersect(ray) {
-                if dist < distance {
-                    // Update the closest intersection information.
-                    distance = dist;
-                    material = shape.material();
-                    hit_point = ray.origin + (ray.direction * distance);
-                    normal = shape.normal(hit_point);
-                }
-            }
-        }
+    // Calculate the intersection of a ray with the objects of the world and return
+    // information about the closest intersection farther than 't_min' and no
+    // farther than 't_max', if any.
+    pub fn intersect(ray: Ray, world: &World, t_min: f64, t_max: f64) -> Option<Intersection> {
+        // Find the closest object, or return None if nothing is hit.
+        let (shape, hit) = world.closest(ray, t_min, t_max)?;
+        let material = shape.material();
 
-        if distance < f64::INFINITY {
-            Some(Intersection {
-                distance,
-                hit_point,
-                normal,
-                material,
-            })
-        } else {
-            None // No intersection found
-        }
+        Some(Intersection {
+            distance: hit.distance,
+            hit_point: hit.point,
+            normal: material.shading_normal(&hit),
+            geometric_normal: hit.geometric_normal,
+            material: material.at(hit.uv, hit.local_point),
+            front_face: hit.front_face,
+            uv: hit.uv,
+            object: hit.object,
+        })
     }
 
-    // Cast a ray into the scene, compute shading, and handle reflections.
+    // Cast a ray into the scene, compute shading, and handle reflections and
+    // refractions. Rays leaving the scene take the color of the environment.
+    // Area lights take their shadow rays at random from 'rng'.
     pub fn cast_ray(
         ray: Ray,
-        objects: &[Box<dyn Intersectable>],
+        world: &World,
         lights: &[Light],
+        environment: &Environment,
         options: &Options,
         depth: u8,
+        rng: &mut Rng,
     ) -> Option<Color> {
         if depth >= options.max_rays {
             return None; // Maximum recursion depth reached
         }
 
-        let intersection = Ray::intersect(ray, objects)?;
+        // Area lights in front of the closest object are seen instead of it.
+        let intersection = Ray::intersect(ray, world, crate::EPSILON, f64::INFINITY);
+        let reach = intersection
+            .as_ref()
+            .map_or(f64::INFINITY, |hit| hit.distance);
+        if let Some(glow) = Light::glow(lights, ray, reach) {
+            return Some(glow);
+        }
+        // Rays leaving the scene see the environment.
+        let Some(intersection) = intersection else {
+            return Some(environment.color(ray.direction));
+        };
+        let material = &intersection.material;
+
+        let mut shaded_color =
+            Light::shade(world, lights, options, &intersection, ray.direction, rng);
+
+        // Glowing materials add their own light, which reflections and
+        // refractions of them carry along too.
+        shaded_color += material.emitted();
+
+        // Turn the normals towards the side the ray came from. New rays start
+        // off the surface itself, which bumps and normal maps do not move.
+        let (facing, surface) = if intersection.front_face {
+            (intersection.normal, intersection.geometric_normal)
+        } else {
+            (-intersection.normal, -intersection.geometric_normal)
+        };
+
+        // Transparent materials reflect part of the light passing through them.
+        let mut reflectiveness = material.reflectiveness;
+
+        if material.transparency > 0.0 && options.refractions {
+            let eta = if intersection.front_face {
+                material.index_of_refraction.recip()
+            } else {
+                material.index_of_refraction
+            };
+
+            match ray.direction.refract(facing, eta) {
+                // Light that cannot leave the material is reflected back.
+                None => reflectiveness += material.transparency,
+                Some(refraction) => {
+                    let reflected = fresnel(-ray.direction.dot(facing), eta);
+                    reflectiveness += material.transparency * reflected;
 
-        let mut shaded_color = Light::shade(objects, lights, options, intersection, ray.direction);
+                    let refracted_ray = Ray {
+                        origin: intersection.hit_point.correct(-surface),
+                        direction: refraction.normalize(),
+                    };
 
-        if intersection.material.reflectiveness > 0.0 && options.reflections {
+                    if let Some(refracted_color) = Ray::cast_ray(
+                        refracted_ray,
+                        world,
+                        lights,
+                        environment,
+                        options,
+                        depth + 1,
+                        rng,
+                    ) {
+                        // The material tints the light entering it.
+                        let tint = if intersection.front_face {
+                            material.color
+                        } else {
+                            Color::white()
+                        };
+                        shaded_color +=
+                            refracted_color * tint * (material.transparency * (1.0 - reflected));
+                    }
+                }
+            }
+        }
+
+        if reflectiveness > 0.0 && options.reflections {
             let reflection = ray.direction.reflect(intersection.normal).normalize();
 
             let reflected_ray = Ray {
-                origin: intersection.hit_point.correct(intersection.normal),
+                origin: intersection.hit_point.correct(surface),
                 direction: reflection,
             };
 
-            if let Some(reflected_color) =
-                Ray::cast_ray(reflected_ray, objects, lights, options, depth + 1)
-            {
-                shaded_color += reflected_color * intersection.material.reflectiveness;
+            if let Some(reflected_color) = Ray::cast_ray(
+                reflected_ray,
+                world,
+                lights,
+                environment,
+                options,
+                depth + 1,
+                rng,
+            ) {
+                shaded_color += reflected_color * reflectiveness;
             }
         }
 
         Some(shaded_color)
     }
-}
\ No newline at end of file
+}
+
+// Return the share of light reflected by a transparent surface, following the
+// Fresnel equations for unpolarized light. 'cos_incident' is the cosine of the
+// angle between the ray and the normal, and 'eta' the ratio of the indices of
+// refraction as for Vec3::refract.
+pub(crate) fn fresnel(cos_incident: f64, eta: f64) -> f64 {
+    let sin2_refracted = eta * eta * (1.0 - cos_incident * cos_incident);
+    if sin2_refracted >= 1.0 {
+        return 1.0;
+    }
+    let cos_refracted = (1.0 - sin2_refracted).sqrt();
+
+    let perpendicular = (eta * cos_incident - cos_refracted) / (eta * cos_incident + cos_refracted);
+    let parallel = (cos_incident - eta * cos_refracted) / (cos_incident + eta * cos_refracted);
+    (perpendicular * perpendicular + parallel * parallel) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Return the direction at 'degrees' from the normal (0, 1, 0), travelling
+    // down against it, and the sine of that angle.
+    fn incoming(degrees: f64) -> (Vec3, f64) {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        (Vec3::new(sin, -cos, 0.0), sin)
+    }
+
+    #[test]
+    fn normal_incidence_reflects_the_fresnel_share() {
+        for index in [1.33_f64, 1.5, 2.42] {
+            let expected = ((index - 1.0) / (index + 1.0)).powi(2);
+            assert!((fresnel(1.0, index.recip()) - expected).abs() < 1e-12);
+            // The same share comes back from inside the material.
+            assert!((fresnel(1.0, index) - expected).abs() < 1e-12);
+        }
+        // Grazing light is all reflected.
+        assert!((fresnel(0.0, 1.0 / 1.5) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn light_past_the_critical_angle_is_totally_reflected() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let eta: f64 = 1.5; // Leaving glass into air
+        let critical = eta.recip().asin().to_degrees();
+
+        let (before, _) = incoming(critical - 0.5);
+        assert!(before.refract(normal, eta).is_some());
+        assert!(fresnel(-before.dot(normal), eta) < 1.0);
+
+        for degrees in [critical + 0.5, 60.0, 89.0] {
+            let (past, _) = incoming(degrees);
+            assert!(past.refract(normal, eta).is_none());
+            assert_eq!(fresnel(-past.dot(normal), eta), 1.0);
+        }
+    }
+
+    #[test]
+    fn refraction_follows_snells_law() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let (n1, n2) = (1.0, 1.5);
+        for degrees in [0.0, 15.0, 30.0, 45.0, 80.0] {
+            let (direction, sin_incident) = incoming(degrees);
+            let refracted = direction
+                .refract(normal, n1 / n2)
+                .expect("light entering glass always refracts");
+            assert!((refracted.length() - 1.0).abs() < 1e-12);
+
+            // The ray goes on through the surface, in the same plane, bent
+            // towards the normal.
+            assert!(refracted.y < 0.0 && refracted.z == 0.0);
+            assert!(refracted.x >= 0.0);
+            let sin_refracted = refracted.x;
+            assert!((n1 * sin_incident - n2 * sin_refracted).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::logic::Rng;
+use std::str::FromStr;
+
+// Define how the sample positions inside a pixel are chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampler {
+    Grid,     // Evenly spaced positions, the same in every pixel
+    Jittered, // One random position in each cell of the grid
+    Halton,   // A low-discrepancy sequence, randomly shifted in every pixel
+}
+
+impl Sampler {
+    // Return 'count' sample positions within the unit square. Grids with a
+    // number of samples that is not a square put more samples on some rows.
+    pub fn samples(&self, count: u32, rng: &mut Rng) -> Vec<(f64, f64)> {
+        let count = count.max(1) as usize;
+        let mut samples = Vec::with_capacity(count);
+
+        match self {
+            Sampler::Grid | Sampler::Jittered => {
+                let rows = ((count as f64).sqrt().round() as usize).max(1);
+                for row in 0..rows {
+                    let columns = count * (row + 1) / rows - count * row / rows;
+                    for column in 0..columns {
+                        let (dx, dy) = match self {
+                            Sampler::Grid => (0.5, 0.5),
+                            _ => (rng.next_f64(), rng.next_f64()),
+                        };
+                        samples.push((
+                            (column as f64 + dx) / columns as f64,
+                            (row as f64 + dy) / rows as f64,
+                        ));
+                    }
+                }
+            }
+            Sampler::Halton => {
+                // Shifting the whole sequence keeps its good distribution
+                // while giving every pixel a different pattern.
+                let (shift_x, shift_y) = (rng.next_f64(), rng.next_f64());
+                for index in 1..=count {
+                    samples.push((
+                        (radical_inverse(index, 2) + shift_x).fract(),
+                        (radical_inverse(index, 3) + shift_y).fract(),
+                    ));
+                }
+            }
+        }
+        samples
+    }
+}
+
+impl FromStr for Sampler {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Sampler, String> {
+        match text {
+            "grid" => Ok(Sampler::Grid),
+            "jittered" => Ok(Sampler::Jittered),
+            "halton" => Ok(Sampler::Halton),
+            _ => Err("expected grid, jittered or halton".to_string()),
+        }
+    }
+}
+
+// Define how the samples around a pixel are weighted into its final color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Box,      // Average of the samples within the pixel
+    Tent,     // Weights falling linearly to zero one pixel away
+    Gaussian, // Bell curve with a standard deviation of half a pixel
+    Mitchell, // Mitchell-Netravali cubic, sharper with slight negative lobes
+}
+
+impl Filter {
+    // Return how far from the pixel center, in pixels, samples are taken.
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    // Return the weight of a sample at the given offset from the pixel center
+    // along one axis. The filters weigh both axes separately.
+    fn weight(&self, offset: f64) -> f64 {
+        let x = offset.abs();
+        if x >= self.radius() {
+            return 0.0;
+        }
+
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - x,
+            // Shift the curve down so it reaches zero at the radius.
+            Filter::Gaussian => (-2.0 * x * x).exp() - (-2.0 * 1.5 * 1.5_f64).exp(),
+            Filter::Mitchell => {
+                const B: f64 = 1.0 / 3.0;
+                const C: f64 = 1.0 / 3.0;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x * x * x
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else {
+                    ((-B - 6.0 * C) * x * x * x
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Filter, String> {
+        match text {
+            "box" => Ok(Filter::Box),
+            "tent" => Ok(Filter::Tent),
+            "gaussian" => Ok(Filter::Gaussian),
+            "mitchell" => Ok(Filter::Mitchell),
+            _ => Err("expected box, tent, gaussian or mitchell".to_string()),
+        }
+    }
+}
+
+// Number of steps in the tabulated distribution of a filter.
+const FILTER_BINS: usize = 64;
+
+// Define the distribution of a filter along one axis, tabulated so samples can
+// be placed where the filter weighs the most. Each sample then counts the
+// same, up to the sign of the filter, instead of wasting rays on the edges of
+// the filter where the weights are small.
+#[derive(Debug)]
+pub struct FilterTable {
+    radius: f64,
+    signs: Vec<f64>, // Sign of the filter over each step
+    cdf: Vec<f64>,   // Share of the filter's absolute weight before each step
+}
+
+impl FilterTable {
+    pub fn new(filter: Filter) -> FilterTable {
+        let radius = filter.radius();
+        let step = 2.0 * radius / FILTER_BINS as f64;
+        let weights: Vec<f64> = (0..FILTER_BINS)
+            .map(|bin| filter.weight(-radius + (bin as f64 + 0.5) * step))
+            .collect();
+
+        let mut cdf = Vec::with_capacity(FILTER_BINS + 1);
+        let mut total = 0.0;
+        cdf.push(0.0);
+        for weight in &weights {
+            total += weight.abs();
+            cdf.push(total);
+        }
+        for value in &mut cdf {
+            *value /= total;
+        }
+
+        FilterTable {
+            radius,
+            signs: weights.iter().map(|weight| weight.signum()).collect(),
+            cdf,
+        }
+    }
+
+    // Map a number in [0, 1) to an offset from the pixel center, along with
+    // the sign of the filter there.
+    pub fn sample(&self, u: f64) -> (f64, f64) {
+        // Find the step the number falls in, then where it falls in the step.
+        let bin = self.cdf[1..]
+            .partition_point(|&value| value <= u)
+            .min(FILTER_BINS - 1);
+        let width = self.cdf[bin + 1] - self.cdf[bin];
+        let fraction = if width > 0.0 {
+            ((u - self.cdf[bin]) / width).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        let step = 2.0 * self.radius / FILTER_BINS as f64;
+        (
+            -self.radius + (bin as f64 + fraction) * step,
+            self.signs[bin],
+        )
+    }
+}
+
+// Return the random number generator for the samples of a pixel, so each
+// pixel gets the same samples for a given seed whichever thread renders it.
+pub fn pixel_rng(seed: u64, x: u32, y: u32) -> Rng {
+    let mut scramble = Rng::new(seed);
+    Rng::new(scramble.next_u64() ^ ((u64::from(y) << 32) | u64::from(x)))
+}
+
+// Mirror the digits of 'index' in the given base around the decimal point.
+fn radical_inverse(mut index: usize, base: usize) -> f64 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f64;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLERS: [Sampler; 3] = [Sampler::Grid, Sampler::Jittered, Sampler::Halton];
+    const FILTERS: [Filter; 4] = [
+        Filter::Box,
+        Filter::Tent,
+        Filter::Gaussian,
+        Filter::Mitchell,
+    ];
+
+    #[test]
+    fn samplers_give_the_requested_count() {
+        let mut rng = Rng::new(7);
+        for sampler in SAMPLERS {
+            for count in [1, 2, 3, 5, 6, 7, 8, 10, 17] {
+                let samples = sampler.samples(count, &mut rng);
+                assert_eq!(samples.len(), count as usize, "{:?}", sampler);
+                assert!(samples
+                    .iter()
+                    .all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)));
+            }
+            assert_eq!(sampler.samples(0, &mut rng).len(), 1);
+        }
+
+        // Six samples make two rows of three.
+        let grid = Sampler::Grid.samples(6, &mut rng);
+        let expected: Vec<(f64, f64)> = [0.25, 0.75]
+            .iter()
+            .flat_map(|&y| [1.0 / 6.0, 0.5, 5.0 / 6.0].map(|x| (x, y)))
+            .collect();
+        for (sample, expected) in grid.iter().zip(&expected) {
+            assert!((sample.0 - expected.0).abs() < 1e-12 && sample.1 == expected.1);
+        }
+    }
+
+    #[test]
+    fn filter_offsets_stay_within_the_radius() {
+        for filter in FILTERS {
+            let table = FilterTable::new(filter);
+            for step in 0..1000 {
+                let (offset, _) = table.sample(step as f64 / 1000.0);
+                assert!(offset.abs() <= filter.radius(), "{:?}: {}", filter, offset);
+            }
+            let (first, _) = table.sample(0.0);
+            let (last, _) = table.sample(0.999_999);
+            assert!(first < -0.9 * filter.radius() && last > 0.9 * filter.radius());
+        }
+    }
+
+    #[test]
+    fn mitchell_keeps_its_negative_lobes() {
+        let table = FilterTable::new(Filter::Mitchell);
+        let mut negative = 0;
+        for step in 0..1000 {
+            let (offset, sign) = table.sample(step as f64 / 1000.0);
+            let weight = Filter::Mitchell.weight(offset);
+            if weight.abs() > 1e-3 {
+                assert_eq!(sign, weight.signum(), "at {}", offset);
+            }
+            if sign < 0.0 {
+                negative += 1;
+                assert!(offset.abs() > 1.0);
+            }
+        }
+        assert!(negative > 0);
+
+        // The other filters are never negative.
+        for filter in [Filter::Box, Filter::Tent, Filter::Gaussian] {
+            let table = FilterTable::new(filter);
+            assert!((0..1000).all(|step| table.sample(step as f64 / 1000.0).1 > 0.0));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_samples() {
+        for sampler in SAMPLERS {
+            let first = sampler.samples(16, &mut pixel_rng(42, 10, 20));
+            let again = sampler.samples(16, &mut pixel_rng(42, 10, 20));
+            assert_eq!(first, again, "{:?}", sampler);
+            if sampler != Sampler::Grid {
+                assert_ne!(first, sampler.samples(16, &mut pixel_rng(43, 10, 20)));
+                assert_ne!(first, sampler.samples(16, &mut pixel_rng(42, 11, 20)));
+            }
+        }
+    }
+}
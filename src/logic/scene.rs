@@ -1,69 +1,220 @@
-use crate::logic::Camera;
+use crate::logic::output;
+use crate::logic::pixel_rng;
 use crate::logic::Color;
+use crate::logic::Environment;
+use crate::logic::Error;
+use crate::logic::FilterTable;
 use crate::logic::Intersectable;
 use crate::logic::Light;
 use crate::logic::Options;
-use crate::logic::Ray;
-use std::fs::File;
-use std::io::prelude::*;
+use crate::logic::OutputFormat;
+use crate::logic::Projection;
+use crate::logic::ToneMapper;
+use crate::logic::World;
+use std::any::Any;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+// Width and height in pixels of the tiles handed out to the rendering threads.
+const TILE_SIZE: u32 = 32;
 
 #[derive(Debug)]
 pub struct Scene {
     pub width: u32,
     pub height: u32,
 
-    pub camera: Camera,
+    pub camera: Box<dyn Projection>,
     pub objects: Vec<Box<dyn Intersectable>>,
     pub lights: Vec<Light>,
-    pub bg_color: Color,
+    pub environment: Environment,
     pub options: Options,
 }
 
 impl Scene {
-    // Render the scene and save the result to an image file.
-    pub fn render(&self, filename: String) {
-        // Create an image buffer with the specified dimensions.
-        let mut imgbuf = image::ImageBuffer::new(self.width, self.height);
-
-        // Calculate gamma correction factor reciprocally.
-        let gamma_correction = self.options.gamma.recip();
-
-        // Convert width and height to floating-point numbers.
-        let w = f64::from(self.width);
-        let h = f64::from(self.height);
-
-        // Create a ppm file to save the rendering result.
-        let name = filename.clone().split_once(".").unwrap().0.to_owned() + ".ppm";
-        let mut file = File::create(name).unwrap();
-        file.write_all("P3\n".as_bytes()).unwrap();
-        let scale = format!("{} {}\n", self.width, self.height);
-        file.write_all(scale.as_bytes()).unwrap();
-        file.write_all(b"255\n").unwrap();
-
-        // Iterate over the coordinates and pixels of the image.
-        for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-            // Calculate normalized coordinates within the image.
-            let u = f64::from(x) / w;
-            let v = f64::from(y) / h;
+    // Render the scene and save the result to an image file, in the format
+    // of the options or else the one matching the extension of the filename.
+    // High dynamic range formats get the light as it was computed, the
+    // others the light tone mapped as the options ask.
+    pub fn render<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.render_cancellable(path, &AtomicBool::new(false))
+    }
+
+    // Render the scene as 'render' does, stopping with Error::Cancelled once
+    // 'cancel' is set, for instance from another thread. Nothing is written
+    // when rendering is cancelled.
+    pub fn render_cancellable<P: AsRef<Path>>(
+        &self,
+        path: P,
+        cancel: &AtomicBool,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+        let format = self
+            .options
+            .format
+            .or_else(|| OutputFormat::from_path(path))
+            .ok_or_else(|| Error::UnsupportedFormat(path.to_path_buf()))?;
+
+        // Trace every pixel, row by row.
+        let mut colors = self.render_pixels(cancel)?;
+
+        // Tone map the light once, before it is quantized.
+        if !format.is_hdr() {
+            let tone_mapper = ToneMapper::new(&self.options);
+            for color in colors.iter_mut() {
+                *color = tone_mapper.apply(*color);
+            }
+        }
+
+        output::write_image(
+            path,
+            self.width,
+            self.height,
+            &colors,
+            format,
+            &self.options,
+        )
+        .map_err(|err| Error::Io(path.to_path_buf(), err))
+    }
+
+    // Compute the color of every pixel, in row order. The image is split into
+    // tiles which the worker threads take one at a time until none are left.
+    // Each pixel only depends on its own coordinates, so the result is the
+    // same whatever the number of threads. The threads stop between tiles
+    // once 'cancel' is set.
+    pub fn render_pixels(&self, cancel: &AtomicBool) -> Result<Vec<Color>, Error> {
+        let tiles_x = self.width.div_ceil(TILE_SIZE);
+        let tiles_y = self.height.div_ceil(TILE_SIZE);
+        let tile_count = tiles_x as usize * tiles_y as usize;
+        let next_tile = AtomicUsize::new(0);
+        let threads = self.options.thread_count().min(tile_count);
+        let world = World::new(&self.objects);
+        let filter = FilterTable::new(self.options.filter);
+
+        let tiles = thread::scope(|scope| -> Result<Vec<(usize, Vec<Color>)>, Error> {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                            if tile >= tile_count || cancel.load(Ordering::Relaxed) {
+                                return done;
+                            }
+                            let x = (tile as u32 % tiles_x) * TILE_SIZE;
+                            let y = (tile as u32 / tiles_x) * TILE_SIZE;
+                            done.push((tile, self.render_tile(&world, &filter, x, y)));
+                        }
+                    })
+                })
+                .collect();
+
+            // Join every thread before reporting one that panicked, as the
+            // scope would panic itself over a thread left unjoined.
+            let joined: Vec<_> = workers.into_iter().map(|worker| worker.join()).collect();
+            let mut tiles = Vec::new();
+            for result in joined {
+                tiles.extend(result.map_err(|panic| Error::Panicked(panic_message(&*panic)))?);
+            }
+            Ok(tiles)
+        })?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+
+        // Copy the tiles into place.
+        let mut pixels = vec![Color::black(); self.width as usize * self.height as usize];
+        for (tile, colors) in tiles {
+            let x0 = (tile as u32 % tiles_x) * TILE_SIZE;
+            let y0 = (tile as u32 / tiles_x) * TILE_SIZE;
+            let tile_width = TILE_SIZE.min(self.width - x0) as usize;
+
+            for (row, line) in colors.chunks(tile_width).enumerate() {
+                let start = ((y0 as usize + row) * self.width as usize) + x0 as usize;
+                pixels[start..start + tile_width].copy_from_slice(line);
+            }
+        }
+        Ok(pixels)
+    }
 
-            // Generate a ray from the camera at the given coordinates.
-            let ray = self.camera.get_ray(u, v);
+    // Compute the colors of the tile starting at the given pixel, in row order.
+    fn render_tile(&self, world: &World, filter: &FilterTable, x0: u32, y0: u32) -> Vec<Color> {
+        let x1 = (x0 + TILE_SIZE).min(self.width);
+        let y1 = (y0 + TILE_SIZE).min(self.height);
+        let mut colors = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                colors.push(self.render_pixel(world, filter, x, y));
+            }
+        }
+        colors
+    }
 
-            // Cast a ray into the scene and compute the resulting color.
-            let color = Ray::cast_ray(ray, &self.objects, &self.lights, &self.options, 0)
-                .unwrap_or(self.bg_color);
+    // Compute the color of a single pixel by tracing rays around its center,
+    // placed according to the reconstruction filter.
+    fn render_pixel(&self, world: &World, filter: &FilterTable, x: u32, y: u32) -> Color {
+        let options = &self.options;
+        let mut rng = pixel_rng(options.seed, x, y);
+        let mut color = Color::black();
+        let mut average = Color::black();
+        let mut total_weight = 0.0;
 
-            // Apply gamma correction and set the pixel color.
-            *pixel = color.gamma_rgb(gamma_correction);
+        let samples = options.sampler.samples(options.samples, &mut rng);
 
-            // Add the color data to the ppm file.
-            let colors = color.gamma_rgb(gamma_correction);
-            file.write_all(format!("{} {} {}\n", colors[0], colors[1], colors[2]).as_bytes())
-                .unwrap();
-            file.write_all(b"\n").unwrap();
+        // Spread the samples over the lens of the camera as well, in another
+        // order so the positions in the pixel and on the lens do not line up.
+        let mut lens = vec![(0.5, 0.5); samples.len()];
+        if self.camera.aperture() > 0.0 {
+            lens = options.sampler.samples(options.samples, &mut rng);
+            rng.shuffle(&mut lens);
         }
 
-        // Save the rendered image to the specified file.
-        imgbuf.save(filename).unwrap();
+        for (&(sx, sy), &lens) in samples.iter().zip(&lens) {
+            let (dx, sign_x) = filter.sample(sx);
+            let (dy, sign_y) = filter.sample(sy);
+            let weight = sign_x * sign_y;
+
+            // Calculate normalized coordinates within the image.
+            let u = (f64::from(x) + 0.5 + dx) / f64::from(self.width);
+            let v = (f64::from(y) + 0.5 + dy) / f64::from(self.height);
+
+            // Generate a ray from the camera and cast it into the scene. Parts
+            // of the image the camera does not see stay black.
+            let sample = match self.camera.get_lens_ray(u, v, lens) {
+                Some(ray) => options.integrator.radiance(
+                    ray,
+                    world,
+                    &self.lights,
+                    &self.environment,
+                    options,
+                    &mut rng,
+                ),
+                None => Color::black(),
+            };
+
+            color += sample * weight;
+            average += sample;
+            total_weight += weight;
+        }
+
+        // Samples from the negative lobes of a filter can cancel out the
+        // others, in which case the plain average is the better estimate.
+        if total_weight > 0.0 {
+            color * total_weight.recip()
+        } else {
+            average * (samples.len() as f64).recip()
+        }
+    }
+}
+
+// Return the message a thread panicked with, as given to 'panic!'.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
     }
 }
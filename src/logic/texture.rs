@@ -0,0 +1,396 @@
+use crate::logic::Color;
+use crate::logic::Rng;
+use crate::logic::Vec3;
+use image::codecs::hdr::HdrDecoder;
+use image::DynamicImage;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+// Define a trait named 'Texture' for colors varying over the surface of an
+// object. Textures are shared between the rendering threads, so they must be
+// Send and Sync.
+pub trait Texture: Debug + Send + Sync {
+    // This method returns the color at a point of a surface, given by its
+    // texture coordinates and by its position in the object's own space, so
+    // solid textures move along with transformed objects.
+    fn color(&self, uv: (f64, f64), point: Vec3) -> Color;
+}
+
+// Define which texture coordinate a pattern changes along.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UvAxis {
+    U, // Around spheres and cylinders, across the faces of cubes
+    V, // From the bottom to the top of spheres and cylinders
+}
+
+impl UvAxis {
+    fn pick(&self, uv: (f64, f64)) -> f64 {
+        match self {
+            UvAxis::U => uv.0,
+            UvAxis::V => uv.1,
+        }
+    }
+}
+
+impl FromStr for UvAxis {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<UvAxis, String> {
+        match text {
+            "u" => Ok(UvAxis::U),
+            "v" => Ok(UvAxis::V),
+            _ => Err("expected u or v".to_string()),
+        }
+    }
+}
+
+// Define a checkerboard with 'scale' squares per unit of texture coordinates.
+#[derive(Debug)]
+pub struct Checker {
+    pub even: Color,
+    pub odd: Color,
+    pub scale: f64,
+}
+
+impl Texture for Checker {
+    fn color(&self, uv: (f64, f64), _point: Vec3) -> Color {
+        let squares = (uv.0 * self.scale).floor() + (uv.1 * self.scale).floor();
+        if squares.rem_euclid(2.0) < 1.0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+// Define alternating stripes, 'scale' pairs of stripes per unit of texture
+// coordinates along the axis.
+#[derive(Debug)]
+pub struct Stripes {
+    pub even: Color,
+    pub odd: Color,
+    pub scale: f64,
+    pub axis: UvAxis,
+}
+
+impl Texture for Stripes {
+    fn color(&self, uv: (f64, f64), _point: Vec3) -> Color {
+        let stripe = (self.axis.pick(uv) * self.scale * 2.0).floor();
+        if stripe.rem_euclid(2.0) < 1.0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+// Define a blend from one color to another as a texture coordinate goes from
+// 0 to 1.
+#[derive(Debug)]
+pub struct Gradient {
+    pub from: Color,
+    pub to: Color,
+    pub axis: UvAxis,
+}
+
+impl Texture for Gradient {
+    fn color(&self, uv: (f64, f64), _point: Vec3) -> Color {
+        self.from.mix(self.to, self.axis.pick(uv).clamp(0.0, 1.0))
+    }
+}
+
+// Define a cloudy blend of two colors following fractal Perlin noise, with
+// features about 1 / 'scale' units across. More octaves add finer detail.
+#[derive(Debug)]
+pub struct Noise {
+    pub from: Color,
+    pub to: Color,
+    pub scale: f64,
+    pub octaves: u32,
+}
+
+impl Texture for Noise {
+    fn color(&self, _uv: (f64, f64), point: Vec3) -> Color {
+        let value = 0.5 * (1.0 + fbm(point * self.scale, self.octaves));
+        self.from.mix(self.to, value.clamp(0.0, 1.0))
+    }
+}
+
+// Define marble, veins running across the x axis every 2π / 'scale' units,
+// bent by noise as strong as 'turbulence'.
+#[derive(Debug)]
+pub struct Marble {
+    pub from: Color,
+    pub to: Color,
+    pub scale: f64,
+    pub turbulence: f64,
+    pub octaves: u32,
+}
+
+impl Texture for Marble {
+    fn color(&self, _uv: (f64, f64), point: Vec3) -> Color {
+        let point = point * self.scale;
+        let phase = point.x + self.turbulence * turbulence(point, self.octaves);
+        self.from.mix(self.to, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+// Define wood, rings around the y axis 1 / 'scale' units apart, made uneven
+// by noise as strong as 'turbulence'.
+#[derive(Debug)]
+pub struct Wood {
+    pub from: Color,
+    pub to: Color,
+    pub scale: f64,
+    pub turbulence: f64,
+}
+
+impl Texture for Wood {
+    fn color(&self, _uv: (f64, f64), point: Vec3) -> Color {
+        let point = point * self.scale;
+        let distance = (point.x * point.x + point.z * point.z).sqrt();
+        let rings = distance + self.turbulence * fbm(point, 4);
+        self.from.mix(self.to, 0.5 - 0.5 * (2.0 * PI * rings).cos())
+    }
+}
+
+// Define a texture read from an image file, repeated 'scale' times per unit
+// of texture coordinates. The pixels are shared between copies of the
+// texture, so an image used by several materials is stored once.
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Arc<[Color]>,
+    pub scale: f64,
+}
+
+impl ImageTexture {
+    // Load the image at 'path' as colors. 8-bit and 16-bit images are stored
+    // sRGB encoded, so they are decoded to the linear values colors in scene
+    // files are given in. Floating point images such as .hdr are used as they
+    // are stored.
+    pub fn open(path: &Path) -> Result<ImageTexture, image::ImageError> {
+        ImageTexture::load(path, true)
+    }
+
+    // Load the image at 'path' as data, such as a bump or normal map, whose
+    // pixel values are used as they are stored.
+    pub fn open_data(path: &Path) -> Result<ImageTexture, image::ImageError> {
+        ImageTexture::load(path, false)
+    }
+
+    fn load(path: &Path, srgb: bool) -> Result<ImageTexture, image::ImageError> {
+        let (width, height, pixels) = read_image(path, srgb)?;
+        if width == 0 || height == 0 {
+            return Err(empty_image());
+        }
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+            scale: 1.0,
+        })
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> Color {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    // Blend the four pixels around the point. The bottom of the image is at
+    // v = 0 and the image repeats in both directions.
+    fn color(&self, uv: (f64, f64), _point: Vec3) -> Color {
+        let x = uv.0 * self.scale * self.width as f64 - 0.5;
+        let y = (1.0 - uv.1 * self.scale) * self.height as f64 - 0.5;
+        let (left, top) = (x.floor(), y.floor());
+        let (dx, dy) = (x - left, y - top);
+        let (left, top) = (left as isize, top as isize);
+
+        let upper = self.pixel(left, top).mix(self.pixel(left + 1, top), dx);
+        let lower = self
+            .pixel(left, top + 1)
+            .mix(self.pixel(left + 1, top + 1), dx);
+        upper.mix(lower, dy)
+    }
+}
+
+// Return the error for an image without pixels, which cannot be looked up.
+pub(crate) fn empty_image() -> image::ImageError {
+    let error = io::Error::new(io::ErrorKind::InvalidData, "the image has no pixels");
+    image::ImageError::IoError(error)
+}
+
+// Read the pixels of the image at 'path', with its width and height. Radiance
+// .hdr files keep light brighter than white, and other floating point images
+// are used as they are stored. Integer images go from 0 to 1, and are decoded
+// from sRGB to linear values when 'srgb' is set.
+pub(crate) fn read_image(
+    path: &Path,
+    srgb: bool,
+) -> Result<(usize, usize, Arc<[Color]>), image::ImageError> {
+    let is_hdr = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+    let (width, height, pixels, encoded): (u32, u32, Vec<[f32; 3]>, bool) = if is_hdr {
+        // Opened as other images, .hdr files would be squeezed into 0..1.
+        let reader = BufReader::new(File::open(path).map_err(image::ImageError::IoError)?);
+        let decoder = HdrDecoder::new(reader)?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+        let pixels = pixels.into_iter().map(|pixel| pixel.0).collect();
+        (metadata.width, metadata.height, pixels, false)
+    } else {
+        let image = image::open(path)?;
+        let float = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let image = image.into_rgb32f();
+        let (width, height) = image.dimensions();
+        let pixels = image.pixels().map(|pixel| pixel.0).collect();
+        (width, height, pixels, srgb && !float)
+    };
+
+    let decode = |value: f32| {
+        let value = f64::from(value);
+        if !encoded {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let pixels = pixels
+        .into_iter()
+        .map(|[r, g, b]| Color::new(decode(r), decode(g), decode(b)))
+        .collect();
+    Ok((width as usize, height as usize, pixels))
+}
+
+// Return fractal noise: Perlin noise summed over 'octaves' octaves, each twice
+// as fine and half as strong as the previous one. The result stays between -1
+// and 1.
+fn fbm(point: Vec3, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut point = point;
+    for _ in 0..octaves.max(1) {
+        sum += amplitude * perlin(point);
+        total += amplitude;
+        amplitude *= 0.5;
+        point = point * 2.0;
+    }
+    sum / total
+}
+
+// Return the sum of the magnitudes of the octaves of noise, giving the sharp
+// creases used for marble.
+fn turbulence(point: Vec3, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut point = point;
+    for _ in 0..octaves.max(1) {
+        sum += amplitude * perlin(point).abs();
+        amplitude *= 0.5;
+        point = point * 2.0;
+    }
+    sum
+}
+
+// Return the shuffled numbers from 0 to 255 choosing the gradients of the
+// noise, repeated once so lookups need no wrapping. They come from a fixed
+// seed, so images look the same on every run.
+fn permutation() -> &'static [u8; 512] {
+    static TABLE: OnceLock<[u8; 512]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut values: Vec<u8> = (0..=255).collect();
+        Rng::new(0x5eed).shuffle(&mut values);
+        let mut table = [0; 512];
+        for (index, entry) in table.iter_mut().enumerate() {
+            *entry = values[index % 256];
+        }
+        table
+    })
+}
+
+// Return Ken Perlin's improved gradient noise at the point, between about -1
+// and 1, smooth and repeating every 256 units.
+fn perlin(point: Vec3) -> f64 {
+    let table = permutation();
+    let cell = |value: f64| (value.floor() as i64).rem_euclid(256) as usize;
+    let (x, y, z) = (cell(point.x), cell(point.y), cell(point.z));
+    let (fx, fy, fz) = (
+        point.x - point.x.floor(),
+        point.y - point.y.floor(),
+        point.z - point.z.floor(),
+    );
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+
+    // Hash the corners of the cell.
+    let a = table[x] as usize + y;
+    let aa = table[a] as usize + z;
+    let ab = table[a + 1] as usize + z;
+    let b = table[x + 1] as usize + y;
+    let ba = table[b] as usize + z;
+    let bb = table[b + 1] as usize + z;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(
+                u,
+                gradient(table[aa], fx, fy, fz),
+                gradient(table[ba], fx - 1.0, fy, fz),
+            ),
+            lerp(
+                u,
+                gradient(table[ab], fx, fy - 1.0, fz),
+                gradient(table[bb], fx - 1.0, fy - 1.0, fz),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                gradient(table[aa + 1], fx, fy, fz - 1.0),
+                gradient(table[ba + 1], fx - 1.0, fy, fz - 1.0),
+            ),
+            lerp(
+                u,
+                gradient(table[ab + 1], fx, fy - 1.0, fz - 1.0),
+                gradient(table[bb + 1], fx - 1.0, fy - 1.0, fz - 1.0),
+            ),
+        ),
+    )
+}
+
+// Return the dot product of the offset with one of twelve gradient
+// directions, picked by the hash.
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
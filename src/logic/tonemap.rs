@@ -0,0 +1,223 @@
+use crate::logic::Color;
+use crate::logic::Options;
+use std::str::FromStr;
+
+// Define how light brighter than white is brought into the range of the
+// screen. Every operator works on the red, green and blue components apart,
+// so very bright colors fade towards white as they would on film.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    Clip,             // Light above white is cut off, leaving the rest unchanged
+    Reinhard,         // x / (1 + x), never quite reaching white
+    ExtendedReinhard, // Reinhard reaching white at the white point
+    Filmic,           // John Hable's filmic curve from Uncharted 2
+    Aces,             // Stephen Hill's fit of the ACES reference transform
+}
+
+// Define how the tone mapped light is encoded into the values of the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    Gamma, // Power of 1 / gamma, the gamma of the options
+    Srgb,  // The sRGB curve, linear near black, which screens expect
+}
+
+impl ToneMap {
+    // Return the brightness turned into pure white when the options leave the
+    // white point out. The operators without a white point ignore it.
+    pub fn default_white_point(&self) -> f64 {
+        match self {
+            ToneMap::ExtendedReinhard => 4.0,
+            _ => 11.2,
+        }
+    }
+
+    // Map one component of a color, for the operators working on components.
+    fn map(&self, value: f64, white: f64) -> f64 {
+        match self {
+            ToneMap::Clip | ToneMap::Aces => value,
+            ToneMap::Reinhard => value / (1.0 + value),
+            ToneMap::ExtendedReinhard => value * (1.0 + value / (white * white)) / (1.0 + value),
+            // The curve is applied to twice the light, the exposure bias of
+            // the original, and scaled to reach white at the white point.
+            ToneMap::Filmic => filmic(2.0 * value) / filmic(2.0 * white),
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ToneMap, String> {
+        match text {
+            "clip" => Ok(ToneMap::Clip),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "extended_reinhard" => Ok(ToneMap::ExtendedReinhard),
+            "filmic" => Ok(ToneMap::Filmic),
+            "aces" => Ok(ToneMap::Aces),
+            _ => Err("expected clip, reinhard, extended_reinhard, filmic or aces".to_string()),
+        }
+    }
+}
+
+impl Transfer {
+    // Encode a component between 0 and 1.
+    fn encode(&self, value: f64, gamma_correction: f64) -> f64 {
+        match self {
+            Transfer::Gamma => value.powf(gamma_correction),
+            Transfer::Srgb if value <= 0.0031308 => value * 12.92,
+            Transfer::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Transfer, String> {
+        match text {
+            "gamma" => Ok(Transfer::Gamma),
+            "srgb" => Ok(Transfer::Srgb),
+            _ => Err("expected gamma or srgb".to_string()),
+        }
+    }
+}
+
+// Turn the light computed for the pixels into the colors of an image: scale
+// it by the exposure, tone map it, then encode it with the transfer curve.
+#[derive(Debug)]
+pub struct ToneMapper {
+    scale: f64,
+    tone_map: ToneMap,
+    white: f64,
+    transfer: Transfer,
+    gamma_correction: f64,
+}
+
+impl ToneMapper {
+    pub fn new(options: &Options) -> ToneMapper {
+        ToneMapper {
+            scale: options.exposure.exp2(),
+            tone_map: options.tone_map,
+            white: options
+                .white_point
+                .unwrap_or(options.tone_map.default_white_point()),
+            transfer: options.transfer,
+            gamma_correction: options.gamma.recip(),
+        }
+    }
+
+    // Return the displayed color of 'light', with components between 0 and 1.
+    pub fn apply(&self, light: Color) -> Color {
+        let light = light * self.scale;
+        let (r, g, b) = match self.tone_map {
+            ToneMap::Aces => aces(light.components()),
+            tone_map => {
+                let (r, g, b) = light.components();
+                (
+                    tone_map.map(r, self.white),
+                    tone_map.map(g, self.white),
+                    tone_map.map(b, self.white),
+                )
+            }
+        };
+        let encode = |value: f64| {
+            self.transfer
+                .encode(value.clamp(0.0, 1.0), self.gamma_correction)
+        };
+        Color::new(encode(r), encode(g), encode(b))
+    }
+}
+
+// John Hable's filmic curve, before scaling to the white point.
+fn filmic(x: f64) -> f64 {
+    const A: f64 = 0.15; // Shoulder strength
+    const B: f64 = 0.50; // Linear strength
+    const C: f64 = 0.10; // Linear angle
+    const D: f64 = 0.20; // Toe strength
+    const E: f64 = 0.02; // Toe numerator
+    const F: f64 = 0.30; // Toe denominator
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms:
+// the color is taken into the ACES working space, where the curve is applied,
+// and back.
+fn aces((r, g, b): (f64, f64, f64)) -> (f64, f64, f64) {
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let multiply = |m: &[[f64; 3]; 3], (r, g, b): (f64, f64, f64)| {
+        (
+            m[0][0] * r + m[0][1] * g + m[0][2] * b,
+            m[1][0] * r + m[1][1] * g + m[1][2] * b,
+            m[2][0] * r + m[2][1] * g + m[2][2] * b,
+        )
+    };
+    let curve = |v: f64| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+        a / b
+    };
+    let (r, g, b) = multiply(&INPUT, (r, g, b));
+    multiply(&OUTPUT, (curve(r), curve(g), curve(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_points_map_to_white() {
+        let white = ToneMap::ExtendedReinhard.default_white_point();
+        assert_eq!(ToneMap::ExtendedReinhard.map(white, white), 1.0);
+        assert!((ToneMap::ExtendedReinhard.map(7.5, 7.5) - 1.0).abs() < 1e-15);
+
+        let white = ToneMap::Filmic.default_white_point();
+        assert_eq!(ToneMap::Filmic.map(white, white), 1.0);
+        assert_eq!(ToneMap::Filmic.map(3.0, 3.0), 1.0);
+        assert!(ToneMap::Filmic.map(1.0, white) < 1.0);
+    }
+
+    #[test]
+    fn reinhard_never_reaches_white() {
+        assert_eq!(ToneMap::Reinhard.map(1.0, 0.0), 0.5);
+        assert_eq!(ToneMap::Reinhard.map(3.0, 0.0), 0.75);
+        for value in [10.0, 1e3, 1e6] {
+            let mapped = ToneMap::Reinhard.map(value, 0.0);
+            assert!(mapped < 1.0 && mapped > ToneMap::Reinhard.map(value / 10.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn clip_cuts_off_light_above_white() {
+        let options = Options {
+            tone_map: ToneMap::Clip,
+            gamma: 1.0,
+            ..Options::default()
+        };
+        let color = ToneMapper::new(&options).apply(Color::new(0.25, 1.0, 3.0));
+        assert_eq!(color, Color::new(0.25, 1.0, 1.0));
+    }
+
+    #[test]
+    fn srgb_curve_is_continuous() {
+        let threshold = 0.0031308;
+        let linear = threshold * 12.92;
+        let curved = 1.055 * f64::powf(threshold, 1.0 / 2.4) - 0.055;
+        assert!((linear - curved).abs() < 1e-7);
+
+        let encode = |value: f64| Transfer::Srgb.encode(value, 1.0);
+        assert_eq!(encode(threshold), linear);
+        assert!((encode(threshold + 1e-12) - linear).abs() < 1e-7);
+        assert_eq!(encode(0.0), 0.0);
+        assert!((encode(1.0) - 1.0).abs() < 1e-15);
+        assert!((encode(0.5) - 0.735_356_983_052).abs() < 1e-9);
+    }
+}
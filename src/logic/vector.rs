@@ -60,6 +60,20 @@ impl Vec3 {
         *self - other * other.dot(*self) * 2.0
     }
 
+    // Refract the unit vector through a surface with the given unit normal,
+    // which faces against the vector. 'eta' is the index of refraction on the
+    // side the vector comes from divided by the one on the other side.
+    // Returns None when the light is totally reflected instead.
+    pub fn refract(&self, normal: Vec3, eta: f64) -> Option<Vec3> {
+        let cos_incident = -self.dot(normal);
+        let sin2_refracted = eta * eta * (1.0 - cos_incident * cos_incident);
+        if sin2_refracted > 1.0 {
+            return None;
+        }
+        let cos_refracted = (1.0 - sin2_refracted).sqrt();
+        Some(*self * eta + normal * (eta * cos_incident - cos_refracted))
+    }
+
     // Correct the vector by nudging it slightly in a given direction.
     pub fn correct(&self, other: Vec3) -> Vec3 {
         *self + (other * crate::EPSILON)
@@ -76,6 +90,32 @@ impl Vec3 {
     pub(crate) fn splat(value: f64) -> Vec3 {
         Vec3::new(value, value, value)
     }
+
+    // Return the component along the given axis (0 for x, 1 for y, 2 for z).
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    // Return the component-wise reciprocal of the vector.
+    pub fn recip(&self) -> Vec3 {
+        Vec3::new(self.x.recip(), self.y.recip(), self.z.recip())
+    }
+
+    // Return two unit vectors perpendicular to this unit vector and to each
+    // other, such that the first crossed with the second gives this one.
+    pub fn basis(&self) -> (Vec3, Vec3) {
+        let helper = if self.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let first = (helper - *self * helper.dot(*self)).normalize();
+        (first, self.cross(first))
+    }
 }
 
 impl Add for Vec3 {
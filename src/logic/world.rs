@@ -0,0 +1,170 @@
+use crate::logic::Bvh;
+use crate::logic::Hit;
+use crate::logic::Intersectable;
+use crate::logic::Ray;
+
+// Define the objects of a scene prepared for fast ray queries. Objects with a
+// bounding box are kept in a bounding volume hierarchy, while unbounded ones
+// such as planes are tested one by one.
+#[derive(Debug)]
+pub struct World<'a> {
+    objects: &'a [Box<dyn Intersectable>],
+    bvh: Bvh,
+    unbounded: Vec<usize>,
+}
+
+impl<'a> World<'a> {
+    // Build the bounding volume hierarchy over the objects.
+    pub fn new(objects: &'a [Box<dyn Intersectable>]) -> World<'a> {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => bounded.push((index, bounds)),
+                None => unbounded.push(index),
+            }
+        }
+
+        World {
+            objects,
+            bvh: Bvh::build(bounded),
+            unbounded,
+        }
+    }
+
+    // Prepare the objects without a hierarchy, so every ray tests every object.
+    pub fn linear(objects: &'a [Box<dyn Intersectable>]) -> World<'a> {
+        World {
+            objects,
+            bvh: Bvh::default(),
+            unbounded: (0..objects.len()).collect(),
+        }
+    }
+
+    pub fn objects(&self) -> &'a [Box<dyn Intersectable>] {
+        self.objects
+    }
+
+    // Find the closest object hit by the ray farther than 't_min' and no
+    // farther than 't_max', and the hit itself. Ties go to the object listed
+    // first, as if the objects were tested in order.
+    pub fn closest(
+        &self,
+        ray: Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(&'a dyn Intersectable, Hit)> {
+        let objects = self.objects;
+        let mut closest = self.bvh.intersect(&ray, t_max, |index, t_max| {
+            objects[index]
+                .intersect(ray, t_min, t_max)
+                .map(|hit| (hit.distance, hit))
+        });
+
+        for &index in &self.unbounded {
+            if let Some(hit) = objects[index].intersect(ray, t_min, t_max) {
+                let closer = match closest {
+                    None => true,
+                    Some((best, best_hit)) => {
+                        hit.distance < best_hit.distance
+                            || (hit.distance == best_hit.distance && index < best)
+                    }
+                };
+                if closer {
+                    closest = Some((index, hit));
+                }
+            }
+        }
+
+        closest.map(|(index, mut hit)| {
+            hit.object = index;
+            (objects[index].as_ref(), hit)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::Color;
+    use crate::logic::Material;
+    use crate::logic::Rng;
+    use crate::logic::Vec3;
+    use crate::objects::{Plane, Sphere};
+
+    fn random_point(rng: &mut Rng, size: f64) -> Vec3 {
+        Vec3::new(
+            rng.range(-size, size),
+            rng.range(-size, size),
+            rng.range(-size, size),
+        )
+    }
+
+    // Find the closest hit by testing every object in order, keeping the
+    // first of equally close ones.
+    fn brute_force(
+        objects: &[Box<dyn Intersectable>],
+        ray: Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(usize, f64)> {
+        let mut closest: Option<(usize, f64)> = None;
+        for (index, object) in objects.iter().enumerate() {
+            if let Some(hit) = object.intersect(ray, t_min, t_max) {
+                if closest.is_none_or(|(_, distance)| hit.distance < distance) {
+                    closest = Some((index, hit.distance));
+                }
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn hierarchy_finds_the_same_hits_as_a_linear_scan() {
+        let mut rng = Rng::new(7);
+        let mut objects: Vec<Box<dyn Intersectable>> = (0..300)
+            .map(|_| {
+                let sphere: Box<dyn Intersectable> = Box::new(Sphere {
+                    position: random_point(&mut rng, 20.0),
+                    radius: rng.range(0.1, 2.0),
+                    material: Material::matt(Color::white()),
+                });
+                sphere
+            })
+            .collect();
+        objects.insert(
+            150,
+            Box::new(Plane {
+                position: Vec3::new(0.0, -5.0, 0.0),
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                material: Material::matt(Color::white()),
+            }),
+        );
+        let world = World::new(&objects);
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let origin = random_point(&mut rng, 30.0);
+            let direction = random_point(&mut rng, 1.0);
+            if direction.length() < 1e-3 {
+                continue;
+            }
+            let ray = Ray {
+                origin,
+                direction: direction.normalize(),
+            };
+            let t_max = if rng.next_f64() < 0.5 {
+                f64::INFINITY
+            } else {
+                rng.range(1.0, 40.0)
+            };
+
+            let found = world
+                .closest(ray, crate::EPSILON, t_max)
+                .map(|(_, hit)| (hit.object, hit.distance));
+            assert_eq!(found, brute_force(&objects, ray, crate::EPSILON, t_max));
+            hits += found.is_some() as usize;
+        }
+        assert!(hits > 1000, "too few rays hit anything to compare");
+    }
+}
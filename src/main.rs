@@ -1,138 +1,102 @@
+mod bench;
+mod cli;
 mod logic;
 mod objects;
 pub use crate::logic::create_spotlight;
 // Define a constant 'EPSILON' with a value of 1e-6, used for numeric tolerance.
 const EPSILON: f64 = 1e-6;
 
+use std::path::Path;
+use std::process::ExitCode;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Instant;
 
-fn main() {
-    //run external python script
-    std::process::Command::new("pip3")
-    .arg("install")
-    .arg("-r")
-    .arg("requirements.txt")
-    .output()
-    .expect("failed to execute process");
+fn main() -> ExitCode {
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\nFor more information, try '--help'.", err);
+            return ExitCode::from(2);
+        }
+    };
 
-    std::process::Command::new("python3")
-        .arg("./ai.py")
-        .output()
-        .expect("failed to execute process");
+    match command {
+        cli::Command::Help(usage) => {
+            print!("{}", usage);
+            ExitCode::SUCCESS
+        }
+        cli::Command::Version => {
+            println!("rt {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        cli::Command::Render(args) => exit_status(render(args)),
+        cli::Command::Bench { spheres, rays } => {
+            bench::run(&spheres, rays);
+            ExitCode::SUCCESS
+        }
+        cli::Command::Audit { output_dir } => {
+            exit_status(with_progress_bar(|| create_audit_pictures(&output_dir)))
+        }
+    }
+}
 
-    let options = logic::Options {
-        max_rays: 4,
-        gamma: 1.0,
-        diffuse: true,
-        specular: true,
-        shadows: true,
-        reflections: true,
-    };
+// Report a failed command and turn its result into the exit status.
+fn exit_status(result: Result<(), logic::Error>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let width = 1920;
-    let height = 1080;
-    let aspect_ratio = width as f64 / height as f64;
+// Load the requested scene file, apply the command line overrides and render it.
+fn render(args: cli::RenderArgs) -> Result<(), logic::Error> {
+    let mut file = logic::SceneFile::load(&args.scene)?;
 
-    let scene = logic::Scene {
-        width,
-        height,
-        camera: logic::Camera::new(
-            logic::Vec3::new(0., 0., 0.), // Positioning the camera further back and centered
-            logic::Vec3::new(10., 4., -20.), // Making the camera look at a point centered among the objects
-            aspect_ratio,
-            0.,
-        ),
-        objects: vec![
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(11.0, 4.0, -15.0),
-                radius: 4.,
-                material: logic::Material::plastic(logic::Color::orange()),
-            }),
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(6.0, 0.0, -11.0),
-                radius: 1.5,
-                material: logic::Material::plastic(logic::Color::cyan()),
-            }),
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(2.0, -1.0, -23.0),
-                radius: 0.5,
-                material: logic::Material::pearl(),
-            }),
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(3.0, -1.5, -21.0),
-                radius: 0.5,
-                material: logic::Material::pearl(),
-            }),
-            Box::new(objects::Cylinder {
-                center: logic::Vec3::new(2.0, -4.0, -19.0),
-                radius: 4.,
-                height: 2.,
-                material: logic::Material::mirror(),
-            }),
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(5.0, 4.0, -15.0),
-                radius: 3.,
-                material: logic::Material::mirror(),
-            }),
-            //walls
-            Box::new(objects::Sphere {
-                position: logic::Vec3::new(0.0, 0.0, 0.0),
-                radius: 50.,
-                material: logic::Material::matt(logic::Color::new(0.2, 0.2, 0.2)),
-            }),
-            Box::new(objects::Plane {
-                position: logic::Vec3::new(0.0, -8.0, 0.0),
-                normal: logic::Vec3::new(0.0, -1.0, 0.0),
-                material: logic::Material::matt(logic::Color::from_u8(0x66, 0x33, 0x66)),
-            }),
-            Box::new(objects::Plane {
-                position: logic::Vec3::new(0.0, 21.0, 0.0),
-                normal: logic::Vec3::new(0.0, 1.0, 0.0),
-                material: logic::Material::matt(logic::Color::from_u8(0x66, 0x33, 0x66)),
-            }),
-        ],
-        lights: vec![
-            logic::Light {
-                light_type: logic::LightType::Ambient,
-                position: logic::Vec3::zero(),
-                intensity: 1.,
-                color: logic::Color::white(),
-            },
-            logic::create_spotlight(
-                logic::Vec3::new(-40.0, 20.0, 20.0), // Positioning the spotlight
-                logic::Vec3::new(10., 4., -20.), // Making the spotlight look at a point centered among the objects
-                1.,
-                logic::Color::white(),
-                10.,
-            ),
-            logic::create_spotlight(
-                logic::Vec3::new(20.0, -5.0, 0.0),
-                logic::Vec3::new(10.0, 4.0, -20.0),
-                1.,
-                logic::Color::purple(),
-                40.,
-            ),
-            logic::create_spotlight(
-                logic::Vec3::new(25.0, 6.0, 10.0),
-                logic::Vec3::new(10.0, 4.0, -19.0),
-                1.,
-                logic::Color::yellow(),
-                40.,
-            ),
-            logic::create_spotlight(
-                logic::Vec3::new(-20.0, -2.0, 10.0),
-                logic::Vec3::new(2.0, -4.0, -19.0),
-                1.,
-                logic::Color::yellow(),
-                40.,
+    file.width = args.width.unwrap_or(file.width);
+    file.height = args.height.unwrap_or(file.height);
+    let options = &mut file.options;
+    options.max_rays = args.max_rays.unwrap_or(options.max_rays);
+    options.gamma = args.gamma.unwrap_or(options.gamma);
+    options.exposure = args.exposure.unwrap_or(options.exposure);
+    options.tone_map = args.tone_map.unwrap_or(options.tone_map);
+    options.white_point = args.white_point.or(options.white_point);
+    options.transfer = args.transfer.unwrap_or(options.transfer);
+    options.diffuse = args.diffuse.unwrap_or(options.diffuse);
+    options.specular = args.specular.unwrap_or(options.specular);
+    options.shadows = args.shadows.unwrap_or(options.shadows);
+    options.reflections = args.reflections.unwrap_or(options.reflections);
+    options.refractions = args.refractions.unwrap_or(options.refractions);
+    options.threads = args.threads.unwrap_or(options.threads);
+    options.samples = args.samples.unwrap_or(options.samples);
+    options.sampler = args.sampler.unwrap_or(options.sampler);
+    options.filter = args.filter.unwrap_or(options.filter);
+    options.seed = args.seed.unwrap_or(options.seed);
+    options.integrator = args.integrator.unwrap_or(options.integrator);
+    options.format = args.format.or(options.format);
+    options.exr_precision = args.exr_precision.unwrap_or(options.exr_precision);
+    options.exr_compression = args.exr_compression.unwrap_or(options.exr_compression);
+
+    let cameras = file.camera_names().join(", ");
+    let scene = file.into_scene(args.camera.as_deref()).ok_or_else(|| {
+        logic::Error::InvalidScene(
+            args.scene.clone(),
+            format!(
+                "no camera named '{}' (available: {})",
+                args.camera.clone().unwrap_or_default(),
+                cameras
             ),
-        ],
-        bg_color: logic::Color::new(0.4, 0.4, 0.4),
-        options,
-    };
+        )
+    })?;
 
+    with_progress_bar(|| scene.render(&args.output))
+}
+
+// Run a rendering job while showing the progress bar, then report how long it took.
+fn with_progress_bar<T, F: FnOnce() -> T>(job: F) -> T {
     let now = Instant::now();
 
     // Create a channel to communicate between threads
@@ -142,164 +106,36 @@ fn main() {
     thread::spawn(move || {
         logic::progress_bar(rx);
     });
-    
-    create_audit_pictures();
-    scene.render("result.png".to_string());
+
+    let result = job();
 
     let duration = now.elapsed();
     tx.send(duration).unwrap(); // Send the final duration to the progress bar
 
     println!(
-
         "\nIt took {} milliseconds to render the image!.",
         duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
     );
+    result
 }
 
-fn create_audit_pictures() {
-    let options = logic::Options {
-        max_rays: 4,
-        gamma: 1.0,
-        diffuse: true,
-        specular: true,
-        shadows: true,
-        reflections: true,
-    };
-
-    let width = 1920;
-    let height = 1080;
-    let aspect_ratio = width as f64 / height as f64;
-
-    let mut scene = logic::Scene {
-        width,
-        height,
-        camera: logic::Camera::new(
-            logic::Vec3::new(0., 0., 5.0), // Positioning the camera further back and centered
-            logic::Vec3::new(10., 4., -20.), // Making the camera look at a point centered among the objects
-            aspect_ratio,
-            0.,
-        ),
-        objects: vec![Box::new(objects::Sphere {
-            position: logic::Vec3::new(10.0, 4.0, -20.0),
-            radius: 5.,
-            material: logic::Material::plastic(logic::Color::orange()),
-        })],
-        lights: vec![
-            logic::Light {
-                light_type: logic::LightType::Ambient,
-                position: logic::Vec3::zero(),
-                intensity: 1.,
-                color: logic::Color::white(),
-            },
-            logic::create_spotlight(
-                logic::Vec3::new(10.0, 20.0, -20.0),
-                logic::Vec3::new(10., 4., -20.),
-                1.,
-                logic::Color::white(),
-                40.,
-            ),
-            logic::create_spotlight(
-                logic::Vec3::new(-20.0, 15.0, 20.0),
-                logic::Vec3::new(10.0, 4.0, -20.0),
-                1.4,
-                logic::Color::purple(),
-                40.,
-            ),
-            logic::create_spotlight(
-                logic::Vec3::new(20.0, -2.0, 10.0),
-                logic::Vec3::new(10.0, 4.0, -20.0),
-                1.,
-                logic::Color::yellow(),
-                40.,
-            ),
-        ],
-        bg_color: logic::Color::black(),
-        options,
-    };
-    scene.render("picture1.png".to_string());
-    scene.objects = vec![
-        Box::new(objects::Cube {
-            position: logic::Vec3::new(10.0, 4.0, -20.0),
-            dimensions: 6.,
-            material: logic::Material::matt(logic::Color::cyan()),
-            normal: logic::Vec3::new(0.0, 0.0, 0.0),
-        }),
-        Box::new(objects::Plane {
-            position: logic::Vec3::new(0.0, -8.0, 0.0),
-            normal: logic::Vec3::new(0.0, -1.0, 0.0),
-            material: logic::Material::matt(logic::Color::from_u8(0x66, 0x33, 0x66)),
-        }),
-    ];
-    scene.lights[0].intensity = 0.3;
-    scene.render("picture2.png".to_string());
-    scene.objects = vec![
-        Box::new(objects::Cube {
-            position: logic::Vec3::new(20.0, -2.5, -20.0),
-            dimensions: 6.,
-            material: logic::Material::matt(logic::Color::blue()),
-            normal: logic::Vec3::new(0.0, 0.0, 0.0),
-        }),
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(11.0, 4.0, -15.0),
-            radius: 4.,
-            material: logic::Material::plastic(logic::Color::orange()),
-        }),
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(6.0, 0.0, -11.0),
-            radius: 1.5,
-            material: logic::Material::plastic(logic::Color::cyan()),
-        }),
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(2.0, -1.0, -23.0),
-            radius: 0.5,
-            material: logic::Material::pearl(),
-        }),
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(3.0, -1.5, -21.0),
-            radius: 0.5,
-            material: logic::Material::pearl(),
-        }),
-        Box::new(objects::Cylinder {
-            center: logic::Vec3::new(2.0, -4.0, -19.0),
-            radius: 4.,
-            height: 2.,
-            material: logic::Material::mirror(),
-        }),
-        Box::new(objects::Cylinder {
-            center: logic::Vec3::new(8.0, -2.0, -7.0),
-            radius: 1.5,
-            height: 4.,
-            material: logic::Material::plastic(logic::Color::green()),
-        }),
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(5.0, 4.0, -15.0),
-            radius: 3.,
-            material: logic::Material::mirror(),
-        }),
-        //walls
-        Box::new(objects::Sphere {
-            position: logic::Vec3::new(0.0, 0.0, 0.0),
-            radius: 50.,
-            material: logic::Material::matt(logic::Color::new(0.2, 0.2, 0.2)),
-        }),
-        Box::new(objects::Plane {
-            position: logic::Vec3::new(0.0, -8.0, 0.0),
-            normal: logic::Vec3::new(0.0, -1.0, 0.0),
-            material: logic::Material::matt(logic::Color::from_u8(0x66, 0x33, 0x66)),
-        }),
-        Box::new(objects::Plane {
-            position: logic::Vec3::new(0.0, 21.0, 0.0),
-            normal: logic::Vec3::new(0.0, 1.0, 0.0),
-            material: logic::Material::matt(logic::Color::from_u8(0x66, 0x33, 0x66)),
-        }),
+// Render the audit pictures from the scene files embedded in the binary.
+fn create_audit_pictures(output_dir: &Path) -> Result<(), logic::Error> {
+    let pictures = [
+        ("picture1.rt", include_str!("../scenes/audit/picture1.rt"), "front", "picture1.png"),
+        ("picture2.rt", include_str!("../scenes/audit/picture2.rt"), "front", "picture2.png"),
+        ("picture3.rt", include_str!("../scenes/audit/picture3.rt"), "front", "picture3.png"),
+        ("picture3.rt", include_str!("../scenes/audit/picture3.rt"), "side", "picture4.png"),
     ];
-    scene.lights[0].intensity = 1.;
-    scene.render("picture3.png".to_string());
-    scene.camera = logic::Camera::new(
-        logic::Vec3::new(15., 3., -35.0),
-        logic::Vec3::new(10., 4., -20.),
-        aspect_ratio,
-        0.,
-    );
-    scene.render("picture4.png".to_string());
+
+    for (name, source, camera, filename) in pictures {
+        let path = Path::new("scenes/audit").join(name);
+        let invalid = |message: String| logic::Error::InvalidScene(path.clone(), message);
+        let scene = logic::SceneFile::parse(source)
+            .map_err(|err| invalid(err.to_string()))?
+            .into_scene(Some(camera))
+            .ok_or_else(|| invalid(format!("no camera named '{}'", camera)))?;
+        scene.render(output_dir.join(filename))?;
+    }
+    Ok(())
 }
@@ -1,3 +1,5 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
 use crate::logic::Material;
 use crate::logic::Ray;
 use crate::logic::Vec3;
@@ -8,11 +10,34 @@ pub struct Cube {
     pub position: Vec3,
     pub dimensions: f64,
     pub material: Material,
-    pub normal: Vec3,
+}
+
+impl Cube {
+    // Describe the hit 'distance' along the ray, on the face across 'axis'
+    // whose outward normal points along 'side' (1 or -1). The texture covers
+    // each face once, upright on the sides and unmirrored seen from outside.
+    fn hit(&self, ray: Ray, distance: f64, axis: usize, side: f64) -> Hit {
+        let mut normal = [0.0; 3];
+        normal[axis] = side;
+        let normal = Vec3::new(normal[0], normal[1], normal[2]);
+
+        // Pick the directions of u and v on the face, so that u × v points out.
+        let (u_axis, v_axis) = match axis {
+            0 => (Vec3::new(0.0, 0.0, -side), Vec3::new(0.0, 1.0, 0.0)),
+            1 => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -side)),
+            _ => (Vec3::new(side, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        };
+
+        let hit = Hit::new(ray, distance, normal, (0.0, 0.0))
+            .with_derivatives(u_axis * self.dimensions, v_axis * self.dimensions);
+        let local = (hit.point - self.position) * (1.0 / self.dimensions);
+        let uv = (local.dot(u_axis) + 0.5, local.dot(v_axis) + 0.5);
+        Hit { uv, ..hit }
+    }
 }
 
 impl Intersectable for Cube {
-    fn intersect(&self, ray: Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         // Calculate the half-size of the cube
         let half_size = self.dimensions / 2.0;
 
@@ -28,33 +53,48 @@ impl Intersectable for Cube {
         let tz1 = (min_bound.z - ray.origin.z) / ray.direction.z;
         let tz2 = (max_bound.z - ray.origin.z) / ray.direction.z;
 
-        // Find the minimum and maximum t-values for the intersections
-        let t_min = tx1.min(tx2).max(ty1.min(ty2)).max(tz1.min(tz2));
-        let t_max = tx1.max(tx2).min(ty1.max(ty2)).min(tz1.max(tz2));
+        // Find the t-values where the ray enters and leaves each pair of faces
+        let near = [tx1.min(tx2), ty1.min(ty2), tz1.min(tz2)];
+        let far = [tx1.max(tx2), ty1.max(ty2), tz1.max(tz2)];
+
+        // The ray enters the cube through the last pair of faces it enters, and
+        // leaves it through the first pair it leaves
+        let mut entry_axis = 0;
+        let mut exit_axis = 0;
+        for axis in 1..3 {
+            if near[axis] > near[entry_axis] || near[entry_axis].is_nan() {
+                entry_axis = axis;
+            }
+            if far[axis] < far[exit_axis] || far[exit_axis].is_nan() {
+                exit_axis = axis;
+            }
+        }
+        let t_near = near[entry_axis];
+        let t_far = far[exit_axis];
 
         // Check if there's a valid intersection
-        if t_max >= 0.0 && t_min <= t_max {
-            // Intersection exists, return the minimum t-value
-            Some(t_min)
-        } else {
+        if t_near > t_far {
             // No intersection
             None
+        } else if t_near > t_min && t_near <= t_max {
+            // The ray enters the cube through a face turned towards it
+            let side = -ray.direction.axis(entry_axis).signum();
+            Some(self.hit(ray, t_near, entry_axis, side))
+        } else if t_far > t_min && t_far <= t_max {
+            // The ray starts inside the cube and leaves it through a face turned away from it
+            let side = ray.direction.axis(exit_axis).signum();
+            Some(self.hit(ray, t_far, exit_axis, side))
+        } else {
+            // The cube is behind the ray or out of reach
+            None
         }
     }
 
-    fn material(&self) -> Material {
-        self.material
+    fn material(&self) -> &Material {
+        &self.material
     }
 
-    fn normal(&self, point: Vec3) -> Vec3 {
-        let differences = (point - self.position).abs();
-        
-        if differences.x >= differences.y.max(differences.z) {
-            return Vec3::new((point.x - self.position.x).signum(), 0.0, 0.0);
-        } else if differences.y >= differences.z {
-            return Vec3::new(0.0, (point.y - self.position.y).signum(), 0.0);
-        } else {
-            return Vec3::new(0.0, 0.0, (point.z - self.position.z).signum());
-        }
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, Vec3::splat(self.dimensions / 2.0)))
     }    
 }
\ No newline at end of file
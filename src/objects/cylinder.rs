@@ -1,7 +1,10 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
 use crate::logic::Intersectable;
 use crate::logic::Material;
 use crate::logic::Ray;
 use crate::logic::Vec3;
+use std::f64::consts::PI;
 
 // Define a struct for representing a cylinder.
 #[derive(Debug)]
@@ -12,88 +15,94 @@ pub struct Cylinder {
     pub material: Material,
 }
 
+impl Cylinder {
+    // Function to describe the hit 'distance' along the ray, on the given cap
+    // or on the side. The texture wraps once around the side, from the bottom
+    // up, and covers each cap once, unmirrored seen from outside.
+    fn hit(&self, ray: Ray, distance: f64, cap: Option<f64>) -> Hit {
+        let point = ray.origin + (ray.direction * distance) - self.center;
+        match cap {
+            Some(side) => {
+                let uv = (
+                    (point.x / self.radius + 1.0) / 2.0,
+                    (1.0 - side * point.z / self.radius) / 2.0,
+                );
+                let dpdu = Vec3::new(2.0 * self.radius, 0.0, 0.0);
+                let dpdv = Vec3::new(0.0, 0.0, -2.0 * side * self.radius);
+                Hit::new(ray, distance, Vec3::new(0.0, side, 0.0), uv).with_derivatives(dpdu, dpdv)
+            }
+            None => {
+                let normal = Vec3::new(point.x, 0.0, point.z).normalize();
+                let uv = (
+                    (-point.z).atan2(point.x) / (2.0 * PI) + 0.5,
+                    point.y / self.height + 0.5,
+                );
+                let dpdu = Vec3::new(point.z, 0.0, -point.x) * (2.0 * PI);
+                let dpdv = Vec3::new(0.0, self.height, 0.0);
+                Hit::new(ray, distance, normal, uv).with_derivatives(dpdu, dpdv)
+            }
+        }
+    }
+}
+
 // Implement the Intersectable trait for Cylinder.
 impl Intersectable for Cylinder {
     // Function to check for ray-cylinder intersection.
-    fn intersect(&self, ray: Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         let oc = ray.origin - self.center;
+        let half_height = self.height / 2.0;
+
+        // Keep track of the closest intersection, and of the cap it is on
+        // (-1 for the bottom, 1 for the top) unless it is on the side.
+        let mut closest: Option<(f64, Option<f64>)> = None;
+        let mut consider = |t: f64, cap: Option<f64>| {
+            if t > t_min && t <= t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, cap));
+            }
+        };
+
+        // Check for intersection with the sides of the cylinder. A ray
+        // parallel to the axis never crosses them.
         let a = ray.direction.x * ray.direction.x + ray.direction.z * ray.direction.z;
         let b = 2.0 * (oc.x * ray.direction.x + oc.z * ray.direction.z);
         let c = oc.x * oc.x + oc.z * oc.z - self.radius * self.radius;
         let discriminant = b * b - 4.0 * a * c;
 
-        if discriminant > 0.0 {
-            let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
-            let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
-
-            let y1 = ray.origin.y + t1 * ray.direction.y;
-            let y2 = ray.origin.y + t2 * ray.direction.y;
-            let half_height = self.height / 2.0;
-
-            // Initialize a variable to keep track of the closest intersection.
-            let mut closest_t = f64::INFINITY;
-
-            // Check for intersection with the sides of the cylinder.
-            if t1 >= 0.0 && y1 >= self.center.y - half_height && y1 <= self.center.y + half_height {
-                closest_t = t1;
-            }
-            if t2 >= 0.0 && y2 >= self.center.y - half_height && y2 <= self.center.y + half_height {
-                if t2 < closest_t {
-                    closest_t = t2;
-                }
-            }
-
-            // Check for intersection with the top and bottom surfaces.
-            let t3 = (self.center.y - half_height - ray.origin.y) / ray.direction.y;
-            let t4 = (self.center.y + half_height - ray.origin.y) / ray.direction.y;
-
-            if t3 >= 0.0 {
-                let x = ray.origin.x + t3 * ray.direction.x - self.center.x;
-                let z = ray.origin.z + t3 * ray.direction.z - self.center.z;
-                if x * x + z * z <= self.radius * self.radius {
-                    if t3 < closest_t {
-                        closest_t = t3;
-                    }
-                }
-            }
-
-            if t4 >= 0.0 {
-                let x = ray.origin.x + t4 * ray.direction.x - self.center.x;
-                let z = ray.origin.z + t4 * ray.direction.z - self.center.z;
-                if x * x + z * z <= self.radius * self.radius {
-                    if t4 < closest_t {
-                        closest_t = t4;
-                    }
+        if a > 0.0 && discriminant > 0.0 {
+            for t in [
+                (-b - discriminant.sqrt()) / (2.0 * a),
+                (-b + discriminant.sqrt()) / (2.0 * a),
+            ] {
+                if (oc.y + t * ray.direction.y).abs() <= half_height {
+                    consider(t, None);
                 }
             }
+        }
 
-            if closest_t < f64::INFINITY {
-                return Some(closest_t);
+        // Check for intersection with the top and bottom surfaces. A ray
+        // parallel to them gives an infinite or undefined t, which fails the
+        // tests below.
+        for side in [-1.0, 1.0] {
+            let t = (side * half_height - oc.y) / ray.direction.y;
+            let x = oc.x + t * ray.direction.x;
+            let z = oc.z + t * ray.direction.z;
+            if x * x + z * z <= self.radius * self.radius {
+                consider(t, Some(side));
             }
         }
 
-        None
+        closest.map(|(t, cap)| self.hit(ray, t, cap))
     }
 
     // Function to get the material of the cylinder.
-    fn material(&self) -> Material {
-        self.material.clone()
+    fn material(&self) -> &Material {
+        &self.material
     }
 
-    // Function to get the normal vector at a given hit point on the cylinder.
-    fn normal(&self, hit_point: Vec3) -> Vec3 {
-        if (hit_point.y - self.center.y - self.height / 2.0).abs() < 1e-5
-            || (hit_point.y - self.center.y + self.height / 2.0).abs() < 1e-5
-        {
-            return Vec3::new(0.0, (hit_point.y - self.center.y).signum(), 0.0);
-        } else {
-            let normal = Vec3::new(
-                hit_point.x - self.center.x,
-                0.0,
-                hit_point.z - self.center.z,
-            );
-            return normal.normalize();
-        }
+    // Function to get the box enclosing the cylinder.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.height / 2.0, self.radius);
+        Some(Aabb::around(self.center, extent))
     }
 }
 
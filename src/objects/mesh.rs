@@ -0,0 +1,208 @@
+use crate::logic::Aabb;
+use crate::logic::Bvh;
+use crate::logic::Hit;
+use crate::logic::Intersectable;
+use crate::logic::Material;
+use crate::logic::Ray;
+use crate::logic::Vec3;
+use crate::objects::triangle::intersect_triangle;
+use std::sync::Arc;
+
+// Define a struct for representing a triangle mesh. Triangles share their
+// vertices through an index buffer, and the mesh keeps its own bounding
+// volume hierarchy over the triangles so large meshes stay fast to render.
+// The geometry is shared between clones of the mesh, so a model can be placed
+// many times, or drawn with another material, without copying it.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    data: Arc<MeshData>,
+    material: Material,
+}
+
+#[derive(Clone, Debug)]
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,   // One normal per vertex, or none for flat shading
+    uvs: Vec<(f64, f64)>, // One texture coordinate per vertex, or none
+    triangles: Vec<[usize; 3]>,
+    bvh: Bvh,
+    bounds: Aabb,
+}
+
+impl Mesh {
+    // Create a flat shaded mesh from the vertex positions and the indices of
+    // the three vertices of each triangle, given counter-clockwise.
+    pub fn new(positions: Vec<Vec3>, triangles: Vec<[usize; 3]>, material: Material) -> Mesh {
+        assert!(
+            triangles
+                .iter()
+                .flatten()
+                .all(|&index| index < positions.len()),
+            "mesh triangles must only use existing vertices"
+        );
+
+        let boxes: Vec<(usize, Aabb)> = triangles
+            .iter()
+            .enumerate()
+            .map(|(index, triangle)| {
+                let [a, b, c] = triangle.map(|vertex| positions[vertex]);
+                (index, Aabb::new(a, a).grow(b).grow(c))
+            })
+            .collect();
+        let bounds = boxes
+            .iter()
+            .fold(Aabb::empty(), |acc, (_, bounds)| acc.union(*bounds));
+
+        Mesh {
+            data: Arc::new(MeshData {
+                positions,
+                normals: Vec::new(),
+                uvs: Vec::new(),
+                triangles,
+                bvh: Bvh::build(boxes),
+                bounds,
+            }),
+            material,
+        }
+    }
+
+    // Use the given per-vertex normals, interpolated across each triangle.
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Mesh {
+        assert_eq!(
+            normals.len(),
+            self.data.positions.len(),
+            "a mesh needs one normal per vertex"
+        );
+        Arc::make_mut(&mut self.data).normals =
+            normals.iter().map(|normal| normal.normalize()).collect();
+        self
+    }
+
+    // Attach texture coordinates to the vertices.
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Mesh {
+        assert_eq!(
+            uvs.len(),
+            self.data.positions.len(),
+            "a mesh needs one texture coordinate per vertex"
+        );
+        Arc::make_mut(&mut self.data).uvs = uvs;
+        self
+    }
+
+    // Replace the material of the whole mesh.
+    pub fn with_material(mut self, material: Material) -> Mesh {
+        self.material = material;
+        self
+    }
+
+    // Compute smooth per-vertex normals by averaging the normals of the
+    // triangles around each vertex, weighted by their area.
+    pub fn smooth(self) -> Mesh {
+        let mut normals = vec![Vec3::zero(); self.data.positions.len()];
+        for triangle in &self.data.triangles {
+            let [a, b, c] = triangle.map(|vertex| self.data.positions[vertex]);
+            // The length of the cross product is twice the triangle's area.
+            let face_normal = (b - a).cross(c - a);
+            for &vertex in triangle {
+                normals[vertex] = normals[vertex] + face_normal;
+            }
+        }
+        let normals = normals
+            .into_iter()
+            .map(|normal| {
+                if normal.length() > 0.0 {
+                    normal
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                }
+            })
+            .collect();
+        self.with_normals(normals)
+    }
+
+    fn vertices(&self, triangle: usize) -> [Vec3; 3] {
+        self.data.triangles[triangle].map(|vertex| self.data.positions[vertex])
+    }
+
+    // Return how points of the triangle move with the texture coordinates,
+    // found by solving for the edges in terms of their change in u and v.
+    // Triangles without texture coordinates, or whose coordinates collapse to
+    // a line, fall back to the barycentric coordinates.
+    fn derivatives(&self, triangle: usize) -> (Vec3, Vec3) {
+        let [a, b, c] = self.vertices(triangle);
+        let (edge1, edge2) = (b - a, c - a);
+        if self.data.uvs.is_empty() {
+            return (edge1, edge2);
+        }
+
+        let [uv_a, uv_b, uv_c] = self.data.triangles[triangle].map(|vertex| self.data.uvs[vertex]);
+        let (du1, dv1) = (uv_b.0 - uv_a.0, uv_b.1 - uv_a.1);
+        let (du2, dv2) = (uv_c.0 - uv_a.0, uv_c.1 - uv_a.1);
+        let determinant = du1 * dv2 - dv1 * du2;
+        if determinant.abs() < 1e-12 {
+            return (edge1, edge2);
+        }
+
+        let inverse = determinant.recip();
+        (
+            (edge1 * dv2 - edge2 * dv1) * inverse,
+            (edge2 * du1 - edge1 * du2) * inverse,
+        )
+    }
+}
+
+impl Intersectable for Mesh {
+    // The hit carries the normal of the triangle, and for shading the normal
+    // interpolated from the vertex normals when the mesh has them. Texture
+    // coordinates are interpolated from the vertices, or are the barycentric
+    // coordinates of the hit in the triangle when the mesh has none.
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (triangle, (t, u, v)) = self.data.bvh.intersect(&ray, t_max, |triangle, t_max| {
+            let [a, b, c] = self.vertices(triangle);
+            intersect_triangle(ray, a, b, c, t_min, t_max).map(|found| (found.0, found))
+        })?;
+
+        let [a, b, c] = self.vertices(triangle);
+        let corners = self.data.triangles[triangle];
+        let weights = [1.0 - u - v, u, v];
+
+        let uv = if self.data.uvs.is_empty() {
+            (u, v)
+        } else {
+            corners
+                .iter()
+                .zip(weights)
+                .fold((0.0, 0.0), |(su, sv), (&vertex, weight)| {
+                    let (vu, vv) = self.data.uvs[vertex];
+                    (su + vu * weight, sv + vv * weight)
+                })
+        };
+
+        let (dpdu, dpdv) = self.derivatives(triangle);
+        let hit =
+            Hit::new(ray, t, (b - a).cross(c - a).normalize(), uv).with_derivatives(dpdu, dpdv);
+        if self.data.normals.is_empty() {
+            return Some(hit);
+        }
+
+        let normal = corners
+            .iter()
+            .zip(weights)
+            .fold(Vec3::zero(), |sum, (&vertex, weight)| {
+                sum + self.data.normals[vertex] * weight
+            });
+        Some(hit.with_shading_normal(normal.normalize()))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.data.triangles.is_empty() {
+            None
+        } else {
+            Some(self.data.bounds)
+        }
+    }
+}
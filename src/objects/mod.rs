@@ -3,8 +3,212 @@ mod cube;
 mod plane;
 //mod cube;
 mod cylinder;
+mod triangle;
+mod mesh;
+mod transformed;
 
 pub use sphere::Sphere;
 pub use plane::Plane;
 pub use cube::Cube;
-pub use cylinder::Cylinder;
\ No newline at end of file
+pub use cylinder::Cylinder;
+pub use triangle::Triangle;
+pub use mesh::Mesh;
+pub use transformed::Transformed;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::{Hit, Intersectable, Material, Ray, Transform, Vec3};
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray {
+            origin: Vec3::new(origin.0, origin.1, origin.2),
+            direction: Vec3::new(direction.0, direction.1, direction.2).normalize(),
+        }
+    }
+
+    fn near(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    // Check the hits of a ray reaching the object at 'enter', and leaving it
+    // at 'leave' unless the object is open, against the interval given to
+    // intersect: hits must lie after t_min and no farther than t_max.
+    fn check_interval(object: &dyn Intersectable, ray: Ray, enter: f64, leave: Option<f64>) {
+        let hit = object
+            .intersect(ray, 0.0, f64::INFINITY)
+            .expect("the ray hits");
+        assert!(
+            (hit.distance - enter).abs() < 1e-9,
+            "{} is not {}",
+            hit.distance,
+            enter
+        );
+        assert!(object.intersect(ray, 0.0, enter + 1e-6).is_some());
+        assert!(object.intersect(ray, 0.0, enter - 1e-6).is_none());
+
+        let behind = object.intersect(ray, enter + 1e-6, f64::INFINITY);
+        match leave {
+            Some(leave) => {
+                let hit = behind.expect("the ray leaves the object");
+                assert!(
+                    (hit.distance - leave).abs() < 1e-9,
+                    "{} is not {}",
+                    hit.distance,
+                    leave
+                );
+                assert!(object.intersect(ray, leave + 1e-6, f64::INFINITY).is_none());
+            }
+            None => assert!(behind.is_none()),
+        }
+    }
+
+    // Check what every hit must hold: it lies 'distance' along the ray, its
+    // normals are unit vectors pointing out of the object whichever side the
+    // ray comes from, 'front_face' tells whether the ray travels against
+    // them, and the derivatives point so that dpdu × dpdv points out too.
+    fn check_surface(hit: &Hit, ray: Ray, outward: Vec3, front_face: bool) {
+        assert!(near(hit.point, ray.origin + ray.direction * hit.distance));
+        assert!(
+            near(hit.geometric_normal, outward),
+            "{:?}",
+            hit.geometric_normal
+        );
+        assert!(near(hit.normal, outward), "{:?}", hit.normal);
+        assert_eq!(hit.front_face, front_face);
+        assert!(hit.dpdu.cross(hit.dpdv).dot(outward) > 0.0);
+    }
+
+    // Check a ray going through a closed object: it enters through the front
+    // face at 'enter', across 'outward_in', and leaves through the back face.
+    fn check_closed(
+        object: &dyn Intersectable,
+        ray: Ray,
+        enter: f64,
+        leave: f64,
+        outward_in: Vec3,
+    ) {
+        check_interval(object, ray, enter, Some(leave));
+        let front = object.intersect(ray, 0.0, f64::INFINITY).unwrap();
+        check_surface(&front, ray, outward_in, true);
+        let back = object.intersect(ray, enter + 1e-6, f64::INFINITY).unwrap();
+        check_surface(&back, ray, -outward_in, false);
+        for hit in [front, back] {
+            assert!((0.0..=1.0).contains(&hit.uv.0) && (0.0..=1.0).contains(&hit.uv.1));
+        }
+    }
+
+    #[test]
+    fn sphere_hits() {
+        let sphere = Sphere {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::neutral(),
+        };
+        let ray = ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0));
+        check_closed(&sphere, ray, 4.0, 6.0, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn plane_hits() {
+        // The plane faces away from its normal.
+        let plane = Plane {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, -1.0, 0.0),
+            material: Material::neutral(),
+        };
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let above = ray((0.0, 2.0, 0.0), (0.0, -1.0, 0.0));
+        check_interval(&plane, above, 2.0, None);
+        check_surface(&plane.intersect(above, 0.0, 3.0).unwrap(), above, up, true);
+        let below = ray((0.0, -3.0, 0.0), (0.0, 1.0, 0.0));
+        check_interval(&plane, below, 3.0, None);
+        check_surface(&plane.intersect(below, 0.0, 4.0).unwrap(), below, up, false);
+    }
+
+    #[test]
+    fn cube_hits() {
+        let cube = Cube {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            dimensions: 2.0,
+            material: Material::neutral(),
+        };
+        // One ray towards a face across each axis.
+        for (origin, direction) in [
+            ((5.0, 0.2, 0.3), (-1.0, 0.0, 0.0)),
+            ((0.2, -5.0, 0.3), (0.0, 1.0, 0.0)),
+            ((0.2, 0.3, 5.0), (0.0, 0.0, -1.0)),
+        ] {
+            let ray = ray(origin, direction);
+            check_closed(&cube, ray, 4.0, 6.0, -ray.direction);
+        }
+    }
+
+    #[test]
+    fn cylinder_hits() {
+        let cylinder = Cylinder {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            height: 2.0,
+            material: Material::neutral(),
+        };
+        let side = ray((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0));
+        check_closed(&cylinder, side, 4.0, 6.0, Vec3::new(1.0, 0.0, 0.0));
+        let cap = ray((0.5, 5.0, 0.0), (0.0, -1.0, 0.0));
+        check_closed(&cylinder, cap, 4.0, 6.0, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn triangle_and_mesh_hits() {
+        let corners = [
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let triangle = Triangle {
+            a: corners[0],
+            b: corners[1],
+            c: corners[2],
+            material: Material::neutral(),
+        };
+        let mesh = Mesh::new(corners.to_vec(), vec![[0, 1, 2]], Material::neutral());
+        let front = Vec3::new(0.0, 0.0, 1.0);
+
+        for object in [&triangle as &dyn Intersectable, &mesh] {
+            let ahead = ray((0.1, 0.0, 5.0), (0.0, 0.0, -1.0));
+            check_interval(object, ahead, 5.0, None);
+            check_surface(
+                &object.intersect(ahead, 0.0, 6.0).unwrap(),
+                ahead,
+                front,
+                true,
+            );
+            let behind = ray((0.1, 0.0, -2.0), (0.0, 0.0, 1.0));
+            check_interval(object, behind, 2.0, None);
+            check_surface(
+                &object.intersect(behind, 0.0, 3.0).unwrap(),
+                behind,
+                front,
+                false,
+            );
+
+            let hit = object.intersect(ahead, 0.0, 6.0).unwrap();
+            assert!(hit.uv.0 >= 0.0 && hit.uv.1 >= 0.0 && hit.uv.0 + hit.uv.1 <= 1.0);
+        }
+    }
+
+    #[test]
+    fn transformed_hits_are_in_world_space() {
+        // A sphere of radius 2 around (0, 0, -3).
+        let sphere = Sphere {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::neutral(),
+        };
+        let transform = Transform::identity()
+            .scale(Vec3::new(2.0, 2.0, 2.0))
+            .translate(Vec3::new(0.0, 0.0, -3.0));
+        let transformed = Transformed::new(sphere, transform);
+        let ray = ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0));
+        check_closed(&transformed, ray, 6.0, 10.0, Vec3::new(0.0, 0.0, 1.0));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
 use crate::logic::Material;
 use crate::logic::Ray;
 use crate::logic::Vec3;
@@ -10,8 +12,32 @@ pub struct Plane {
     pub material: Material, // The material of the plane
 }
 
+impl Plane {
+    // Describe the hit 'distance' along the ray. The texture coordinates are
+    // distances from the plane's position along two directions of the plane,
+    // so textures repeat every unit. Seen from the side the plane faces, u
+    // points right of v.
+    fn hit(&self, ray: Ray, distance: f64) -> Hit {
+        let helper = if self.normal.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let u_axis = (helper - self.normal * helper.dot(self.normal)).normalize();
+        let v_axis = u_axis.cross(self.normal);
+
+        let hit = Hit::new(ray, distance, -self.normal, (0.0, 0.0)) // The normal is negated
+            .with_derivatives(u_axis, v_axis);
+        let offset = hit.point - self.position;
+        Hit {
+            uv: (offset.dot(u_axis), offset.dot(v_axis)),
+            ..hit
+        }
+    }
+}
+
 impl Intersectable for Plane {
-    fn intersect(&self, ray: Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         // Calculate the dot product between the plane's normal and the ray's direction
         let denom = self.normal.dot(ray.direction);
 
@@ -22,21 +48,21 @@ impl Intersectable for Plane {
             // Calculate the distance from the ray's origin to the point of intersection
             let distance = v.dot(self.normal) / denom;
 
-            if distance >= 0.0 {
-                Some(distance) // Return the distance if it's greater than or equal to zero
+            if distance > t_min && distance <= t_max {
+                Some(self.hit(ray, distance)) // Return the hit if it's within reach
             } else {
-                None // No intersection if the distance is negative
+                None // No intersection if the plane is behind the ray or out of reach
             }
         } else {
             None // No intersection if the denominator is close to zero (parallel or nearly parallel)
         }
     }
 
-    fn material(&self) -> Material {
-        self.material // Return the material of the plane
+    fn material(&self) -> &Material {
+        &self.material // Return the material of the plane
     }
 
-    fn normal(&self, _point: Vec3) -> Vec3 {
-        -self.normal // Return the negated normal vector
+    fn bounding_box(&self) -> Option<Aabb> {
+        None // A plane is infinite
     }
 }
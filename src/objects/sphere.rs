@@ -1,7 +1,10 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
 use crate::logic::Intersectable;
 use crate::logic::Material;
 use crate::logic::Ray;
 use crate::logic::Vec3;
+use std::f64::consts::PI;
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -10,8 +13,33 @@ pub struct Sphere {
     pub material: Material,
 }
 
+impl Sphere {
+    // Describe the hit 'distance' along the ray. The texture wraps around the
+    // sphere once, u following the longitude and v rising from the bottom
+    // pole to the top one.
+    fn hit(&self, ray: Ray, distance: f64) -> Hit {
+        let point = ray.origin + (ray.direction * distance);
+        let normal = (point - self.position).normalize();
+        let u = (-normal.z).atan2(normal.x) / (2.0 * PI) + 0.5;
+        let v = 1.0 - normal.y.clamp(-1.0, 1.0).acos() / PI;
+
+        // u runs eastwards along the parallel and v northwards along the
+        // meridian. Both are undefined at the poles, where the circle of
+        // latitude shrinks to a point.
+        let ring = (normal.x * normal.x + normal.z * normal.z).sqrt();
+        let dpdu = Vec3::new(normal.z, 0.0, -normal.x) * (2.0 * PI * self.radius);
+        let dpdv = if ring > crate::EPSILON {
+            let north = Vec3::new(-normal.y * normal.x / ring, ring, -normal.y * normal.z / ring);
+            north * (PI * self.radius)
+        } else {
+            Vec3::splat(0.0)
+        };
+        Hit::new(ray, distance, normal, (u, v)).with_derivatives(dpdu, dpdv)
+    }
+}
+
 impl Intersectable for Sphere {
-    fn intersect(&self, ray: Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         // oc represents the vector from the spheres center to the light source origin
         let oc = ray.origin - self.position;
         // a represents the length of the ray squared. Is 1 when ray.direction is normalized
@@ -28,31 +56,31 @@ impl Intersectable for Sphere {
         if discriminant > 0.0 {
             t = (-b - discriminant.sqrt()) / a;
 
-            if t < f64::INFINITY && t > crate::EPSILON {
+            if t <= t_max && t > t_min {
                 // if the solution to the equation falls within our decided span, return the result temp
                 // temp is the distance of the ray to the intersection point of the sphere
-                return Some(t);
+                return Some(self.hit(ray, t));
             }
         }
 
-        // if the smaller root falls outside EPSILON and INFINITY, try the bigger root
+        // if the smaller root falls outside t_min and t_max, try the bigger root
         t = (-b + discriminant.sqrt()) / a;
 
-        if t < f64::INFINITY && t > crate::EPSILON {
+        if t <= t_max && t > t_min {
             // if the solution to the equation falls within our decided span, return the result temp
             // temp is the distance of the ray to the intersection point of the sphere
-            return Some(t);
+            return Some(self.hit(ray, t));
         }
 
         // if no solution is acceptable return None. Ray doesn't hit the sphere
         None
     }
 
-    fn material(&self) -> Material {
-        self.material
+    fn material(&self) -> &Material {
+        &self.material
     }
 
-    fn normal(&self, point: Vec3) -> Vec3 {
-        (point - self.position).normalize()
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, Vec3::splat(self.radius)))
     }
 }
@@ -0,0 +1,90 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
+use crate::logic::Intersectable;
+use crate::logic::Material;
+use crate::logic::Ray;
+use crate::logic::Transform;
+use crate::logic::Vec3;
+
+// Define an object moved, turned or stretched by a transform. Rays are brought
+// into the space of the wrapped object, which is intersected as if it had not
+// moved, and normals and surface directions are brought back into the world.
+//
+// Wrapping an 'Arc' lets the same object, such as a large mesh, appear many
+// times with different transforms while its geometry is stored only once.
+#[derive(Debug)]
+pub struct Transformed<T: Intersectable> {
+    object: T,
+    transform: Transform,
+    bounds: Option<Aabb>,
+}
+
+impl<T: Intersectable> Transformed<T> {
+    pub fn new(object: T, transform: Transform) -> Transformed<T> {
+        // Transforming the corners of the object's box gives a box enclosing
+        // the transformed object, if not always the tightest one.
+        let bounds = object.bounding_box().map(|bounds| {
+            let ends = [bounds.min, bounds.max];
+            let mut result = Aabb::empty();
+            for corner in 0..8 {
+                let point = Vec3::new(
+                    ends[corner & 1].x,
+                    ends[(corner >> 1) & 1].y,
+                    ends[corner >> 2].z,
+                );
+                result = result.grow(transform.point(point));
+            }
+            result
+        });
+
+        Transformed {
+            object,
+            transform,
+            bounds,
+        }
+    }
+
+    // Bring a ray into the space of the object. The direction is normalized
+    // again, so also return how much longer distances are in object space.
+    fn object_ray(&self, ray: Ray) -> (Ray, f64) {
+        let inverse = self.transform.inverse();
+        let direction = inverse.vector(ray.direction);
+        let stretch = direction.length();
+        (
+            Ray {
+                origin: inverse.point(ray.origin),
+                direction: direction * (1.0 / stretch),
+            },
+            stretch,
+        )
+    }
+}
+
+impl<T: Intersectable> Intersectable for Transformed<T> {
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (object_ray, stretch) = self.object_ray(ray);
+        let hit = self
+            .object
+            .intersect(object_ray, t_min * stretch, t_max * stretch)?;
+
+        // Whether the ray hits the outside does not change with the transform.
+        let distance = hit.distance / stretch;
+        Some(Hit {
+            distance,
+            point: ray.origin + (ray.direction * distance),
+            geometric_normal: self.transform.normal(hit.geometric_normal).normalize(),
+            normal: self.transform.normal(hit.normal).normalize(),
+            dpdu: self.transform.vector(hit.dpdu),
+            dpdv: self.transform.vector(hit.dpdv),
+            ..hit
+        })
+    }
+
+    fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
@@ -0,0 +1,78 @@
+use crate::logic::Aabb;
+use crate::logic::Hit;
+use crate::logic::Intersectable;
+use crate::logic::Material;
+use crate::logic::Ray;
+use crate::logic::Vec3;
+
+// Define a struct for representing a single triangle.
+#[derive(Debug)]
+pub struct Triangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub material: Material,
+}
+
+impl Intersectable for Triangle {
+    // The normal is the same over the whole triangle, facing the side from
+    // which the vertices are seen counter-clockwise. The texture coordinates
+    // are the barycentric coordinates of the hit point relative to b and c.
+    fn intersect(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (t, u, v) = intersect_triangle(ray, self.a, self.b, self.c, t_min, t_max)?;
+        let normal = (self.b - self.a).cross(self.c - self.a).normalize();
+        let hit = Hit::new(ray, t, normal, (u, v));
+        Some(hit.with_derivatives(self.b - self.a, self.c - self.a))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.a, self.a).grow(self.b).grow(self.c))
+    }
+}
+
+// Intersect a ray with the triangle (a, b, c) using the Möller-Trumbore
+// algorithm, keeping hits farther than 't_min' and no farther than 't_max'.
+// Returns the distance to the hit along with the barycentric coordinates of
+// the hit point relative to b and c.
+pub(crate) fn intersect_triangle(
+    ray: Ray,
+    a: Vec3,
+    b: Vec3,
+    c: Vec3,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+
+    // The ray is parallel to the triangle.
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let inv_determinant = determinant.recip();
+    let s = ray.origin - a;
+    let u = s.dot(p) * inv_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inv_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_determinant;
+    if t > t_min && t <= t_max {
+        Some((t, u, v))
+    } else {
+        None
+    }
+}